@@ -1,6 +1,18 @@
 use bevy::prelude::*;
 
-use crate::{WinSize, Materials, Background, Velocity, Player, Direction};
+use crate::{Background, Velocity, Player, Direction, WorldScroll,
+    SIMULATION, TIMESTEP};
+use crate::registry::AssetRegistry;
+use crate::tuning::Tuning;
+
+//the layers that drift, back to front. the depth of each is in the
+//tuning file, the z is also how fast it scrolls.
+const LAYERS: [&str; 9] = ["bg_02", "bg_03", "bg_10", "bg_04", "bg_05", "bg_06", "bg_07", "bg_08", "bg_09"];
+
+//which texture a background panel is, so its depth can be tuned.
+pub(crate) struct Layer{
+    pub(crate) id: &'static str,
+}
 
 
 pub struct BackgroundsPlugin;
@@ -12,20 +24,17 @@ impl Plugin for BackgroundsPlugin{
             "background setup",
             SystemStage::single(background_spawn.system()),
           )
-        .add_system(scroll_backgrounds.system())
-        .add_system(flip_backgrounds.system());
+        .add_system_to_stage(SIMULATION, scroll_backgrounds.system().after("update_scroll"))
+        .add_system_to_stage(SIMULATION, flip_backgrounds.system().after("update_scroll"));
     }
 }
 
 fn background_spawn(
     mut commands: Commands,
-    materials: Res<Materials>,
-    win_size: Res<WinSize>,
+    registry: Res<AssetRegistry>,
+    tuning: Res<Tuning>,
     ){
 
-    let top = win_size.h / 2.0;
-    let bottom = -win_size.h / 2.0;
-    let left = win_size.w / 2.0;
     let bg_width = 928.0;
     let left_x = 0.0;
     let right_x = bg_width;
@@ -34,249 +43,38 @@ fn background_spawn(
     commands
         //first layer
         .spawn_bundle(SpriteBundle{
-            material: materials.background.clone(),
-            transform: Transform{
-                translation: Vec3::new(0.0, 0.0, 0.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background);
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_02.clone(),
-            transform: Transform{
-                translation: Vec3::new(left_x, 0.0, 0.5),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-        .insert(Velocity{
-            velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_02.clone(),
-            transform: Transform{
-                translation: Vec3::new(right_x, 0.0, 0.5),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-        .insert(Velocity{
-            velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_03.clone(),
-            transform: Transform{
-                translation: Vec3::new(left_x, 0.0, 0.7),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-            });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_03.clone(),
-            transform: Transform{
-                translation: Vec3::new(right_x, 0.0, 0.7),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-            });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_10.clone(),
-            transform: Transform{
-                translation: Vec3::new(left_x, 0.0, 0.8),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-            });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_10.clone(),
-            transform: Transform{
-                translation: Vec3::new(right_x, 0.0, 0.8),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-            });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_04.clone(),
-            transform: Transform{
-                translation: Vec3::new(left_x, 0.0, 1.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_04.clone(),
-            transform: Transform{
-                translation: Vec3::new(right_x, 0.0, 1.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_05.clone(),
-            transform: Transform{
-                translation: Vec3::new(left_x, 0.0, 1.3),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_05.clone(),
-            transform: Transform{
-                translation: Vec3::new(right_x, 0.0, 1.3),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_06.clone(),
-            transform: Transform{
-                translation: Vec3::new(left_x, 0.0, 1.5),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_06.clone(),
-            transform: Transform{
-                translation: Vec3::new(right_x, 0.0, 1.5),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-        //bg_07 must match bg_06 in z for speed
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_07.clone(),
-            transform: Transform{
-                translation: Vec3::new(left_x, 0.0, 1.5),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_07.clone(),
+            material: registry.material("background"),
             transform: Transform{
-                translation: Vec3::new(right_x, 0.0, 1.5),
+                translation: Vec3::new(0.0, 0.0, tuning.depth("background")),
                 ..Default::default()
             },
             ..Default::default()
         })
         .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_08.clone(),
-            transform: Transform{
-                translation: Vec3::new(left_x, 0.0, 1.6),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_08.clone(),
-            transform: Transform{
-                translation: Vec3::new(right_x, 0.0, 1.6),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_09.clone(),
-            transform: Transform{
-                translation: Vec3::new(left_x, 0.0, 1.9),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
-        });
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.bg_09.clone(),
-            transform: Transform{
-                translation: Vec3::new(right_x, 0.0, 1.9),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Background)
-            .insert(Velocity{
-                velocity: Vec3::new(1.0, 0.0, 0.0),
+        .insert(Layer{
+            id: "background",
         });
+    //two panels a layer so flip_backgrounds can keep leapfrogging them
+    for layer in LAYERS.iter(){
+        for x in [left_x, right_x].iter(){
+            commands
+                .spawn_bundle(SpriteBundle{
+                    material: registry.material(layer),
+                    transform: Transform{
+                        translation: Vec3::new(*x, 0.0, tuning.depth(layer)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(Background)
+                .insert(Layer{
+                    id: *layer,
+                })
+                .insert(Velocity{
+                    velocity: Vec3::new(1.0, 0.0, 0.0),
+                });
+        }
+    }
 }
 
 
@@ -289,27 +87,17 @@ fn get_size(
 }
 
 fn scroll_backgrounds(
-    time: Res<Time>,
+    scroll: Res<WorldScroll>,
     mut bg_query: Query<(&mut Transform, &Velocity),With<Background>>,
-    mut player_query: Query<(&Player)>,
     ){
-    if let Ok((player)) = player_query.single_mut(){
-        //layers will be divided by value in transform.z.  
-        //the further back z (lower) the slower the velocity. 
-        let dir = match player.direction{
-            Direction::Right => -1.0,
-            Direction::Left => 1.0,
-            //Direction::NotMoving => 0.0,
-            _ => 0.0,
-        };
-        let vel = dir * player.vel_mod;
-        for (mut transform, velocity) in bg_query.iter_mut(){
-            //multiplyer is the z value of the background. The further back
-            //into the background, the slower the multiplier.
-            let multiplier = transform.translation.z;
-            transform.translation += 
-                (vel * multiplier) * velocity.velocity * time.delta_seconds();
-        }
+    //layers will be divided by value in transform.z.  
+    //the further back z (lower) the slower the velocity. 
+    for (mut transform, velocity) in bg_query.iter_mut(){
+        //multiplyer is the z value of the background. The further back
+        //into the background, the slower the multiplier.
+        let multiplier = transform.translation.z;
+        transform.translation += 
+            (scroll.speed * multiplier) * velocity.velocity * TIMESTEP;
     }
 }
 
@@ -0,0 +1,440 @@
+use bevy::prelude::*;
+use bevy::sprite::collide_aabb::collide;
+
+use crate::{SCALE_UP, Direction, Velocity, Gravity, Body, Contact, Player, StrikeBox,
+    PlayerAction, Energy, Attacking, Enemy, Proximity, Damage, DamageKind,
+    ApplyStatus, StatusKind, ArenaLock, LevelComplete, Difficulty, Knockback, ENEMYSPEEDSTOP,
+    AppState, SIMULATION, TIMESTEP};
+use crate::registry::AssetRegistry;
+use crate::tuning::{Tuning, BossAnimation, Swing};
+use crate::score::PlayerHit;
+use crate::damage::CritRng;
+
+pub(crate) const BOSS_POWER: i32 = 300;
+//walk speed and wind-up time get worse for the player every phase.
+const BOSS_SPEED: [f32; 3] = [60.0, 85.0, 110.0];
+const BOSS_WINDUP: [f32; 3] = [1.0, 0.8, 0.6];
+const HEAVY_RANGE: f32 = 180.0;
+const STAB_RANGE: f32 = 260.0;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum BossAttack{
+    Heavy,
+    Stab,
+    Spin,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum BossState{
+    Idle,
+    Walk,
+    WindUp(BossAttack),
+    Attack(BossAttack),
+    Taunt,
+    Dying,
+    Dead,
+}
+
+pub(crate) struct Boss{
+    state: BossState,
+    phase: usize, //0, 1, 2
+    engaged: bool, //true once the arena locks
+    struck: bool, //current swing already landed
+    windup: Timer,
+    hit_cooldown: Timer, //so one player swing is one hit
+}
+
+impl Boss{
+    //engaged and still standing. music.rs plays the boss track for this.
+    pub(crate) fn fighting(&self) -> bool{
+        self.engaged && self.state != BossState::Dying && self.state != BossState::Dead
+    }
+}
+
+pub struct BossPlugin;
+impl Plugin for BossPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(start_encounter.system())
+                    .with_system(animate_boss.system())
+                    .with_system(boss_defeat.system())
+                       )
+            .add_system_to_stage(SIMULATION, ai_boss.system())
+            .add_system_to_stage(SIMULATION, boss_hits_player.system().label("deal_damage"))
+            .add_system_to_stage(SIMULATION, player_hits_boss.system().label("deal_damage"))
+            .add_system(end_encounter.system());
+    }
+}
+
+//the level file says where the boss waits. see level.rs
+//he has no StatusEffects, he is immune to burn, freeze, stun and
+//knockdown. a knockback would still shove him, just without the stun.
+pub(crate) fn spawn_boss(
+    commands: &mut Commands,
+    registry: &AssetRegistry,
+    tuning: &Tuning,
+    x: f32,
+    ) -> Entity{
+    commands
+        .spawn_bundle(SpriteSheetBundle{
+            texture_atlas: registry.atlas("minotaur"),
+            transform: Transform{
+                translation: Vec3::new(x, 0.0, 1.8),
+                scale: Vec3::new(SCALE_UP, SCALE_UP, 0.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    .insert(Enemy{
+        action: PlayerAction::Stand,
+        direction: Direction::Left,
+        vel_mod: ENEMYSPEEDSTOP,
+    })
+    .insert(Boss{
+        state: BossState::Idle,
+        phase: 0,
+        engaged: false,
+        struck: false,
+        windup: Timer::from_seconds(BOSS_WINDUP[0], false),
+        hit_cooldown: Timer::from_seconds(0.5, false),
+    })
+    .insert(Timer::from_seconds(tuning.boss_animation.frame_time, true))
+    .insert(Gravity{
+        falling: false,
+    })
+    .insert(Contact{
+        ground: false,
+        side: None,
+        platform: None,
+    })
+    .insert(Body{
+        target_vx: 0.0,
+        accel: 600.0,
+        decel: 2000.0,
+        air_control: 0.2,
+    })
+    .insert(Velocity{
+        velocity: Vec3::new(0.0, 0.0, 0.0),
+    })
+    .insert(Energy{
+        power: BOSS_POWER,
+        max: BOSS_POWER,
+    })
+    .insert(Proximity{
+        near_player: false,
+    })
+    .insert(Attacking{
+        attack: false,
+    })
+    .insert(StrikeBox{
+        h: 60.0,
+        w: 150.0,
+        attack_h: 60.0,
+        attack_w: 220.0,
+    })
+    .id()
+}
+
+//the boss health bar is on the hud. see hud.rs
+fn start_encounter(
+    mut arena: ResMut<ArenaLock>,
+    mut boss_query: Query<(&mut Boss, &Proximity)>,
+    ){
+    for(mut boss, proximity) in boss_query.iter_mut(){
+        if proximity.near_player && !boss.engaged{
+            boss.engaged = true;
+            arena.locked = true;
+        }
+    }
+}
+
+fn ai_boss(
+    difficulty: Res<Difficulty>,
+    mut boss_query: Query<(&mut Boss, &mut Enemy, &Energy, &Transform)>,
+    player_query: Query<&Player>,
+    ){
+    if let Ok(player) = player_query.single(){
+        for(mut boss, mut enemy, energy, transform) in boss_query.iter_mut(){
+            boss.hit_cooldown.tick(std::time::Duration::from_secs_f32(TIMESTEP));
+            if !boss.engaged{
+                continue;
+            }
+
+            //phase changes at 2/3 and 1/3 health. taunt before the next phase.
+            let health = energy.power as f32 / energy.max as f32;
+            let phase = if health <= 0.33{
+                2
+            }else if health <= 0.66{
+                1
+            }else{
+                0
+            };
+            let busy = matches!(boss.state, 
+                BossState::Attack(_) | BossState::Dying | BossState::Dead);
+            if phase > boss.phase && !busy{
+                boss.phase = phase;
+                boss.state = BossState::Taunt;
+            }
+
+            let distance = (player.current_x - transform.translation.x).abs();
+            match boss.state{
+                BossState::Idle | BossState::Walk => {
+                    if distance < HEAVY_RANGE{
+                        //last phase swaps the heavy swing for the spin
+                        let attack = if boss.phase == 2{
+                            BossAttack::Spin
+                        }else{
+                            BossAttack::Heavy
+                        };
+                        boss.state = BossState::WindUp(attack);
+                        let windup = BOSS_WINDUP[boss.phase] * difficulty.telegraph_scale();
+                        boss.windup = Timer::from_seconds(windup, false);
+                    }else if distance < STAB_RANGE && boss.phase > 0{
+                        boss.state = BossState::WindUp(BossAttack::Stab);
+                        let windup = BOSS_WINDUP[boss.phase] * 0.6 * difficulty.telegraph_scale();
+                        boss.windup = Timer::from_seconds(windup, false);
+                    }else{
+                        boss.state = BossState::Walk;
+                    }
+                }
+                BossState::WindUp(attack) => {
+                    boss.windup.tick(std::time::Duration::from_secs_f32(TIMESTEP));
+                    if boss.windup.finished(){
+                        boss.state = BossState::Attack(attack);
+                        boss.struck = false;
+                    }
+                }
+                _ => {}
+            }
+
+            //keep the shared enemy systems in step with the boss state.
+            //a stopped vel_mod keeps scroll_enemy from walking him.
+            match boss.state{
+                BossState::Walk => {
+                    enemy.action = PlayerAction::Walk;
+                    enemy.vel_mod = BOSS_SPEED[boss.phase];
+                }
+                BossState::Attack(_) => {
+                    enemy.action = PlayerAction::Chop;
+                    enemy.vel_mod = ENEMYSPEEDSTOP;
+                }
+                BossState::WindUp(_) => {
+                    enemy.action = PlayerAction::WindUp;
+                    enemy.vel_mod = ENEMYSPEEDSTOP;
+                }
+                _ => {
+                    enemy.action = PlayerAction::Stand;
+                    enemy.vel_mod = ENEMYSPEEDSTOP;
+                }
+            }
+        }
+    }
+}
+
+fn animate_boss(
+    time: Res<Time>,
+    tuning: Res<Tuning>,
+    mut level_events: EventWriter<LevelComplete>,
+    mut query: Query<(&mut Timer, &mut TextureAtlasSprite, &mut Boss)>,
+    ){
+    let frames = &tuning.boss_animation;
+    for(mut timer, mut sprite, mut boss) in query.iter_mut(){
+        timer.tick(time.delta());
+        if timer.finished(){
+            sprite.color = Color::WHITE;
+            match boss.state{
+                BossState::Idle => {
+                    sprite.index = frames.idle.next(sprite.index);
+                }
+                BossState::Walk => {
+                    sprite.index = frames.walk.next(sprite.index);
+                }
+                BossState::Taunt => {
+                    if sprite.index == frames.taunt.last{
+                        boss.state = BossState::Idle;
+                    }else{
+                        sprite.index = frames.taunt.next(sprite.index);
+                    }
+                }
+                BossState::WindUp(_) => {
+                    //raise the axe and hold it, flashing red
+                    sprite.index = frames.wind_up.next(sprite.index);
+                    if (boss.windup.elapsed_secs() * 10.0) as i32 % 2 == 0{
+                        sprite.color = Color::rgb(1.0, 0.3, 0.3);
+                    }
+                }
+                BossState::Attack(attack) => {
+                    let clip = swing(frames, attack).frames;
+                    if sprite.index == clip.last{
+                        boss.state = BossState::Idle;
+                    }else{
+                        sprite.index = clip.next(sprite.index);
+                    }
+                }
+                BossState::Dying => {
+                    if sprite.index == frames.dying.last{
+                        boss.state = BossState::Dead;
+                        level_events.send(LevelComplete);
+                    }else{
+                        sprite.index = frames.dying.next(sprite.index);
+                    }
+                }
+                BossState::Dead => {
+                    sprite.index = frames.dying.last;
+                }
+            }
+        }
+    }
+}
+
+fn swing(frames: &BossAnimation, attack: BossAttack) -> Swing{
+    match attack{
+        BossAttack::Heavy => frames.heavy,
+        BossAttack::Stab => frames.stab,
+        BossAttack::Spin => frames.spin,
+    }
+}
+
+//the attack frames where the axe can actually connect
+fn active_frame(frames: &BossAnimation, attack: BossAttack, index: u32) -> bool{
+    let (first, last) = swing(frames, attack).hits;
+    index >= first && index <= last
+}
+
+fn boss_hits_player(
+    mut damage_events: EventWriter<Damage>,
+    mut status_events: EventWriter<ApplyStatus>,
+    mut knockback_events: EventWriter<Knockback>,
+    tuning: Res<Tuning>,
+    mut boss_query: Query<(&mut Boss, &Transform, &TextureAtlasSprite, &StrikeBox)>,
+    player_query: Query<(Entity, &Player, &Transform, &StrikeBox), Without<Boss>>,
+    ){
+    if let Ok((player_entity, player, player_tf, player_box)) = player_query.single(){
+        for(mut boss, boss_tf, sprite, strike_box) in boss_query.iter_mut(){
+            let attack = match boss.state{
+                BossState::Attack(attack) => attack,
+                _ => continue,
+            };
+            if boss.struck || !active_frame(&tuning.boss_animation, attack, sprite.index){
+                continue;
+            }
+
+            //attack box reaches out the way he is facing. the spin hits both sides.
+            let facing = if boss_tf.rotation == Quat::default(){ 1.0 }else{ -1.0 };
+            let reach = if attack == BossAttack::Spin{ 0.0 }else{ facing * strike_box.h };
+            let collision = collide(
+                boss_tf.translation + Vec3::new(reach, 0.0, 0.0),
+                Vec2::new(strike_box.attack_w, strike_box.attack_h * 2.0),
+                player_tf.translation,
+                Vec2::new(player_box.h, player_box.w),
+               );
+
+            if let Some(_) = collision{
+                boss.struck = true;
+                let facing_boss = match player.direction{
+                    Direction::Right => boss_tf.translation.x > player_tf.translation.x,
+                    Direction::Left => boss_tf.translation.x < player_tf.translation.x,
+                    _ => false,
+                };
+                //the heavy swing goes through a block at half damage
+                let blocked = player.action == PlayerAction::Block && facing_boss;
+                let amount = match (attack, blocked){
+                    (BossAttack::Heavy, true) => 12,
+                    (BossAttack::Heavy, false) => 25,
+                    (BossAttack::Stab, false) => 12,
+                    (BossAttack::Spin, false) => 18,
+                    _ => 0,
+                };
+                if amount > 0{
+                    damage_events.send(Damage{
+                        target: player_entity,
+                        amount,
+                        kind: if blocked{ DamageKind::Blocked }else{ DamageKind::Normal },
+                    });
+                }
+                //even a blocked swing shoves the player back
+                let force = match attack{
+                    BossAttack::Heavy => 450.0,
+                    _ => 300.0,
+                };
+                knockback_events.send(Knockback{
+                    target: player_entity,
+                    from_x: boss_tf.translation.x,
+                    force: if blocked{ force * 0.4 }else{ force },
+                    stun: if blocked{ 0.0 }else{ 0.3 },
+                });
+                if attack == BossAttack::Heavy && !blocked{
+                    status_events.send(ApplyStatus{
+                        target: player_entity,
+                        kind: StatusKind::Knockdown,
+                        duration: 1.0,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn player_hits_boss(
+    mut crits: ResMut<CritRng>,
+    mut damage_events: EventWriter<Damage>,
+    mut hit_events: EventWriter<PlayerHit>,
+    mut boss_query: Query<(Entity, &mut Boss, &Transform, &StrikeBox)>,
+    player_query: Query<(&Player, &Transform, &StrikeBox, &Attacking), Without<Boss>>,
+    ){
+    if let Ok((player, player_tf, player_box, attacking)) = player_query.single(){
+        if !attacking.attack{
+            return;
+        }
+        for(entity, mut boss, boss_tf, strike_box) in boss_query.iter_mut(){
+            if !boss.hit_cooldown.finished(){
+                continue;
+            }
+            if boss.state == BossState::Dying || boss.state == BossState::Dead{
+                continue;
+            }
+            let collision = collide(
+                boss_tf.translation,
+                Vec2::new(strike_box.h, strike_box.w),
+                player_tf.translation,
+                Vec2::new(player_box.attack_h, player_box.attack_w),
+               );
+            if let Some(_) = collision{
+                let (amount, kind) = crits.roll(
+                    if player.action == PlayerAction::Charge{ 8 }else{ 5 });
+                damage_events.send(Damage{
+                    target: entity,
+                    amount,
+                    kind,
+                });
+                hit_events.send(PlayerHit{
+                    points: amount as u32 * 5,
+                });
+                boss.hit_cooldown.reset();
+            }
+        }
+    }
+}
+
+fn boss_defeat(
+    mut query: Query<(&mut Boss, &mut Enemy, &Energy)>,
+    ){
+    for(mut boss, mut enemy, energy) in query.iter_mut(){
+        if energy.power <= 0 && boss.state != BossState::Dying && boss.state != BossState::Dead{
+            boss.state = BossState::Dying;
+            enemy.action = PlayerAction::Stand;
+            enemy.vel_mod = ENEMYSPEEDSTOP;
+        }
+    }
+}
+
+fn end_encounter(
+    mut level_events: EventReader<LevelComplete>,
+    mut arena: ResMut<ArenaLock>,
+    ){
+    for _ in level_events.iter(){
+        arena.locked = false;
+    }
+}
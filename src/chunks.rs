@@ -0,0 +1,265 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::{WinSize, WorldScroll, PlatformKind, Element, SPEEDSLOW, SIMULATION, TIMESTEP};
+use crate::registry::AssetRegistry;
+use crate::platforms::{PlatformSpec, spawn_platform};
+use crate::enemies::{spawn_skeleton, spawn_mage};
+use crate::level::{GROUND_TOP, EnemyKind};
+use crate::tuning::{Tuning, PhysicsTuning};
+use crate::physics::integrate;
+use crate::players::JUMP_LIFT;
+
+const CHUNK_WIDTH: f32 = 600.0;
+const PLATFORM_Z: f32 = 1.9; //platforms scroll this much faster than the player walks
+const HIGHEST_TOP: f32 = 260.0; //keeps the climb on screen
+const REACH_SAFETY: f32 = 0.8; //leave room for a late jump
+const PLATFORM_H: f32 = 20.0;
+const MIN_GAP: f32 = 20.0;
+const DESPAWN_BEHIND: f32 = 1.5; //screen widths
+
+//an endless level. made from a seed so the same seed always builds
+//the same run, one chunk at a time off the right of the screen.
+pub(crate) struct ChunkGenerator{
+    rng: StdRng,
+    next_x: f32, //screen x where the next chunk starts
+    prev_top: f32, //top of the last platform made, above the screen bottom
+}
+
+impl ChunkGenerator{
+    pub(crate) fn new(seed: u64) -> Self{
+        ChunkGenerator{
+            rng: StdRng::seed_from_u64(seed),
+            next_x: CHUNK_WIDTH / 2.0,
+            prev_top: GROUND_TOP,
+        }
+    }
+}
+
+//tags everything a chunk spawned so it can go once it's behind
+struct Chunk;
+
+pub struct ChunksPlugin;
+impl Plugin for ChunksPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_system_to_stage(SIMULATION,
+                generate_chunks.system().label("scroll_world").after("update_scroll"))
+            .add_system_to_stage(SIMULATION, despawn_chunks.system());
+    }
+}
+
+//a walking jump from a standing start, flown one step at a time
+//with the player's own body the way body_step moves him. where he is
+//after each step from where he took off, x in how far the platforms
+//have gone under him.
+fn jump_arc(physics: &PhysicsTuning) -> Vec<Vec2>{
+    let mut body = physics.player_body.body();
+    body.target_vx = SPEEDSLOW;
+    let mut velocity = Vec3::new(0.0, physics.jump_speed, 0.0);
+    let mut at = Vec2::new(0.0, JUMP_LIFT);
+    let mut arc = vec![at];
+    //down past the lowest a chunk ever drops
+    while at.y > -HIGHEST_TOP{
+        let moved = integrate(&body, true, physics.gravity, &mut velocity);
+        at += Vec2::new(moved.x * PLATFORM_Z, moved.y);
+        arc.push(at);
+    }
+    arc
+}
+
+//highest a jump gets above where it started
+fn max_rise(arc: &[Vec2]) -> f32{
+    arc.iter().fold(0.0, |top, at| top.max(at.y))
+}
+
+//how far the platforms move under a jump that comes down dy higher.
+//nothing if the jump never gets that high.
+fn reach(arc: &[Vec2], dy: f32) -> f32{
+    arc.iter().rev().find(|at| at.y >= dy).map(|at| at.x).unwrap_or(0.0)
+}
+
+//what one chunk holds, worked out before anything is spawned.
+//x is on screen, like ChunkGenerator.next_x.
+struct ChunkPlan{
+    platforms: Vec<PlatformSpec>,
+    enemy: Option<(EnemyKind, f32)>,
+}
+
+impl ChunkGenerator{
+    //the next chunk along, starting at next_x
+    fn plan(&mut self, arc: &[Vec2]) -> ChunkPlan{
+        let rise_limit = max_rise(arc) * REACH_SAFETY;
+        let chunk_start = self.next_x;
+        let mut x = chunk_start;
+        let mut platforms = Vec::new();
+
+        while x < chunk_start + CHUNK_WIDTH{
+            let w = self.rng.gen_range(70.0..160.0);
+            //sometimes drop back down to the ground and start a new climb
+            let top = if self.rng.gen_bool(0.2){
+                GROUND_TOP + rise_limit * 0.5
+            }else{
+                let rise = self.rng.gen_range(-rise_limit..rise_limit);
+                (self.prev_top + rise).clamp(GROUND_TOP + 20.0, HIGHEST_TOP)
+            };
+            let dy = top - self.prev_top;
+            let most = reach(arc, dy) * REACH_SAFETY;
+            let gap = if most > MIN_GAP{ self.rng.gen_range(MIN_GAP..most) }else{ most };
+            let kind = match self.rng.gen_range(0..10){
+                0..=5 => PlatformKind::Solid,
+                6..=8 => PlatformKind::OneWay,
+                _ => PlatformKind::Crumbling{
+                    delay: 0.6,
+                },
+            };
+
+            let left = x + gap;
+            platforms.push(PlatformSpec{
+                x: left + w / 2.0,
+                y: top - PLATFORM_H / 2.0,
+                w,
+                h: PLATFORM_H,
+                kind,
+                hidden: false,
+            });
+            self.prev_top = top;
+            x = left + w;
+        }
+
+        //one enemy for every other chunk or so, on the ground
+        let enemy_x = self.rng.gen_range(chunk_start..x);
+        let enemy = match self.rng.gen_range(0..6){
+            0 | 1 => Some(EnemyKind::Skeleton),
+            2 => Some(EnemyKind::FireMage),
+            3 => Some(EnemyKind::WaterMage),
+            _ => None,
+        };
+
+        self.next_x = x;
+        ChunkPlan{
+            platforms,
+            enemy: enemy.map(|kind| (kind, enemy_x)),
+        }
+    }
+}
+
+fn generate_chunks(
+    mut commands: Commands,
+    generator: Option<ResMut<ChunkGenerator>>,
+    scroll: Res<WorldScroll>,
+    registry: Res<AssetRegistry>,
+    tuning: Res<Tuning>,
+    window: Res<WinSize>,
+    ){
+    let mut generator = match generator{
+        Some(generator) => generator,
+        None => return,
+    };
+    generator.next_x += scroll.speed * PLATFORM_Z * TIMESTEP;
+    if generator.next_x >= window.w{
+        return;
+    }
+    let screen_bottom = -(window.h / 2.0);
+    let arc = jump_arc(&tuning.physics);
+
+    //keep a chunk ready past the right edge
+    while generator.next_x < window.w{
+        let plan = generator.plan(&arc);
+        for spec in plan.platforms.iter(){
+            let platform = spawn_platform(&mut commands, &registry, screen_bottom, spec);
+            commands.entity(platform).insert(Chunk);
+        }
+        let enemy = match plan.enemy{
+            Some((EnemyKind::Skeleton, x)) => spawn_skeleton(&mut commands, &registry, &tuning, x),
+            Some((EnemyKind::FireMage, x)) => spawn_mage(&mut commands, &tuning,
+                registry.atlas("mage_fire"), Element::Fire, x),
+            Some((EnemyKind::WaterMage, x)) => spawn_mage(&mut commands, &tuning,
+                registry.atlas("mage_water"), Element::Water, x),
+            _ => continue,
+        };
+        commands.entity(enemy).insert(Chunk);
+    }
+}
+
+fn despawn_chunks(
+    mut commands: Commands,
+    window: Res<WinSize>,
+    query: Query<(Entity, &Transform), With<Chunk>>,
+    ){
+    for(entity, transform) in query.iter(){
+        if transform.translation.x < -window.w * DESPAWN_BEHIND{
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const CHUNKS: usize = 200;
+
+    fn plans(seed: u64, physics: &PhysicsTuning) -> Vec<ChunkPlan>{
+        let arc = jump_arc(physics);
+        let mut generator = ChunkGenerator::new(seed);
+        (0..CHUNKS).map(|_| generator.plan(&arc)).collect()
+    }
+
+    #[test]
+    fn same_seed_same_chunks(){
+        let physics = PhysicsTuning::default();
+        let (first, second) = (plans(7, &physics), plans(7, &physics));
+        for(a, b) in first.iter().zip(second.iter()){
+            assert_eq!(a.platforms, b.platforms);
+            assert_eq!(a.enemy, b.enemy);
+        }
+        let other = plans(8, &physics);
+        assert!(first[0].platforms != other[0].platforms);
+    }
+
+    #[test]
+    fn every_jump_can_be_made(){
+        //a floaty and a heavy tuning as well as the usual one
+        let mut floaty = PhysicsTuning::default();
+        floaty.gravity = 500.0;
+        floaty.player_body.air_control = 0.8;
+        let mut heavy = PhysicsTuning::default();
+        heavy.gravity = 1400.0;
+        heavy.jump_speed = 420.0;
+        heavy.player_body.accel = 600.0;
+        heavy.player_body.air_control = 0.2;
+
+        for physics in [PhysicsTuning::default(), floaty, heavy].iter(){
+            let arc = jump_arc(physics);
+            for seed in 0..20{
+                let mut prev: Option<(f32, f32)> = None; //right edge and top
+                for plan in plans(seed, physics).iter(){
+                    for spec in plan.platforms.iter(){
+                        let top = spec.y + PLATFORM_H / 2.0;
+                        if let Some((right, prev_top)) = prev{
+                            let (gap, dy) = (spec.x - spec.w / 2.0 - right, top - prev_top);
+                            assert!(dy <= max_rise(&arc), "rise of {} from {}", dy, prev_top);
+                            assert!(gap <= reach(&arc, dy), "gap of {} going up {}", gap, dy);
+                        }
+                        prev = Some((spec.x + spec.w / 2.0, top));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn arc_matches_a_plain_jump(){
+        //with no walking it is just the textbook parabola, less a
+        //little from the fixed steps
+        let physics = PhysicsTuning::default();
+        let mut standing = physics.clone();
+        standing.player_body.accel = 0.0;
+        let arc = jump_arc(&standing);
+        let textbook = physics.jump_speed * physics.jump_speed / (2.0 * physics.gravity);
+        assert!((max_rise(&arc) - JUMP_LIFT - textbook).abs() < 5.0);
+        assert!(arc.iter().all(|at| at.x == 0.0));
+    }
+}
@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::{Enemy, Energy, Damage, DamageKind, RestartRun, SIMULATION};
+use crate::boss::Boss;
+
+//the player's hits
+pub(crate) const CHOP_DAMAGE: i32 = 8;
+pub(crate) const CHARGE_DAMAGE: i32 = 12;
+const CRITICAL_CHANCE: f64 = 0.1; //a critical does double
+const CRIT_SEED: u64 = 45;
+
+//criticals come off their own seeded rng, not the thread's, so the
+//same run played the same way crits on the same hits.
+pub(crate) struct CritRng{
+    rng: StdRng,
+}
+
+impl CritRng{
+    pub(crate) fn new(seed: u64) -> Self{
+        CritRng{
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    //how hard one of the player's hits lands, and whether it was a critical.
+    pub(crate) fn roll(&mut self, base: i32) -> (i32, DamageKind){
+        if self.rng.gen_bool(CRITICAL_CHANCE){
+            (base * 2, DamageKind::Critical)
+        }else{
+            (base, DamageKind::Normal)
+        }
+    }
+}
+
+pub struct DamagePlugin;
+impl Plugin for DamagePlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .insert_resource(CritRng::new(CRIT_SEED))
+            .add_system_to_stage(SIMULATION, apply_damage.system().after("deal_damage"))
+            .add_system_to_stage(CoreStage::PostUpdate, defeat_enemy.system())
+            .add_system(reseed_crits.system());
+    }
+}
+
+//everything that sends Damage is labelled deal_damage, so a hit
+//lands in the same step it was dealt in.
+fn apply_damage(
+    mut damage_events: EventReader<Damage>,
+    mut query: Query<&mut Energy>,
+    ){
+    for damage in damage_events.iter(){
+        if let Ok(mut energy) = query.get_mut(damage.target){
+            energy.power -= damage.amount;
+        }
+    }
+}
+
+//out of energy, off the field. the boss has his own death in boss.rs.
+//PostUpdate so nothing queued this frame still points at it.
+fn defeat_enemy(
+    mut commands: Commands,
+    query: Query<(Entity, &Energy), (With<Enemy>, Without<Boss>)>,
+    ){
+    for(entity, energy) in query.iter(){
+        if energy.power <= 0{
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn reseed_crits(
+    mut restart_events: EventReader<RestartRun>,
+    mut crits: ResMut<CritRng>,
+    ){
+    for _ in restart_events.iter(){
+        *crits = CritRng::new(CRIT_SEED);
+    }
+}
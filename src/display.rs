@@ -0,0 +1,197 @@
+use bevy::prelude::*;
+use bevy::render::camera::{Camera, CameraProjection, OrthographicProjection, ScalingMode};
+use bevy::window::{WindowCreated, WindowResized};
+
+use crate::WinSize;
+use crate::registry::AssetRegistry;
+
+//the world is laid out for this many units across and up whatever
+//the window does, so WinSize never changes. the camera scales it.
+pub(crate) const LOGICAL_W: f32 = 900.0;
+pub(crate) const LOGICAL_H: f32 = 700.0;
+const BAR_Z: f32 = 10.0; //over everything in the world
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum ScaleMode{
+    Letterbox, //as big as fits, bars over the rest
+    Integer, //as big as fits in whole screen pixels, so it stays crisp
+    Stretch, //fills the window, out of shape if the window is
+}
+
+//F10 goes through the modes.
+pub(crate) struct Display{
+    pub(crate) mode: ScaleMode,
+}
+
+struct GameCamera;
+
+#[derive(Clone, Copy)]
+enum Edge{
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+//covers what the camera shows past the play area.
+struct Bar{
+    edge: Edge,
+}
+
+//on the root node of a ui. fit_ui keeps it over the play area so
+//nothing is drawn on the bars. ui sizes are in window pixels, they
+//don't scale with the play area.
+pub(crate) struct PlayArea;
+
+//where the play area is in the window, in the window's own units.
+struct UiArea{
+    offset: Vec2,
+    size: Vec2,
+}
+
+pub struct DisplayPlugin;
+impl Plugin for DisplayPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .insert_resource(WinSize{
+                h: LOGICAL_H,
+                w: LOGICAL_W,
+            })
+            .insert_resource(Display{
+                mode: ScaleMode::Letterbox,
+            })
+            .insert_resource(UiArea{
+                offset: Vec2::ZERO,
+                size: Vec2::new(LOGICAL_W, LOGICAL_H),
+            })
+            .add_startup_system(display_setup.system())
+            .add_system(cycle_mode.system().label("cycle_mode"))
+            .add_system(fit_display.system().label("fit_display").after("cycle_mode"))
+            .add_system(fit_ui.system().after("fit_display"));
+    }
+}
+
+fn display_setup(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    ){
+    //fit_display sets the edges once it knows the window
+    let mut camera = OrthographicCameraBundle::new_2d();
+    camera.orthographic_projection.scaling_mode = ScalingMode::None;
+    commands.spawn_bundle(camera).insert(GameCamera);
+
+    for edge in [Edge::Left, Edge::Right, Edge::Bottom, Edge::Top].iter(){
+        commands
+            .spawn_bundle(SpriteBundle{
+                material: registry.material("letterbox"),
+                sprite: Sprite::new(Vec2::ZERO),
+                transform: Transform::from_xyz(0.0, 0.0, BAR_Z),
+                ..Default::default()
+            })
+            .insert(Bar{
+                edge: *edge,
+            });
+    }
+}
+
+fn cycle_mode(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut display: ResMut<Display>,
+    ){
+    if keyboard_input.just_pressed(KeyCode::F10){
+        let mode = match display.mode{
+            ScaleMode::Letterbox => ScaleMode::Integer,
+            ScaleMode::Integer => ScaleMode::Stretch,
+            ScaleMode::Stretch => ScaleMode::Letterbox,
+        };
+        display.mode = mode;
+        //display in info! is tracing's, not the resource
+        info!("scaling: {:?}", mode);
+    }
+}
+
+//the camera shows the play area as big as the mode allows, and
+//however much more the window has room for. the bars hide that.
+fn fit_display(
+    display: Res<Display>,
+    windows: Res<Windows>,
+    mut created_events: EventReader<WindowCreated>,
+    mut resize_events: EventReader<WindowResized>,
+    mut ui_area: ResMut<UiArea>,
+    mut camera_query: Query<(&mut Camera, &mut OrthographicProjection), With<GameCamera>>,
+    mut bar_query: Query<(&Bar, &mut Sprite, &mut Transform)>,
+    ){
+    let resized = created_events.iter().count() + resize_events.iter().count() > 0;
+    if !resized && !display.is_changed(){
+        return;
+    }
+    let window = match windows.get_primary(){
+        Some(window) => window,
+        None => return,
+    };
+
+    //screen pixels, so whole numbers mean whole pixels on a hidpi screen too
+    let size = Vec2::new(window.physical_width() as f32, window.physical_height() as f32);
+    if size.x < 1.0 || size.y < 1.0{
+        return; //minimized
+    }
+    let play = Vec2::new(LOGICAL_W, LOGICAL_H);
+    let fit = (size.x / play.x).min(size.y / play.y);
+    let scale = match display.mode{
+        ScaleMode::Letterbox => Vec2::splat(fit),
+        //a window too small for 1:1 cuts off the edges instead
+        ScaleMode::Integer => Vec2::splat(fit.floor().max(1.0)),
+        ScaleMode::Stretch => size / play,
+    };
+    let half = size / scale / 2.0; //of what the camera shows, in world units
+
+    for(mut camera, mut projection) in camera_query.iter_mut(){
+        projection.left = -half.x;
+        projection.right = half.x;
+        projection.bottom = -half.y;
+        projection.top = half.y;
+        camera.projection_matrix = projection.get_projection_matrix();
+    }
+
+    //a window too small for integer 1:1 shows only some of it
+    let shown = (play * scale).min(size);
+    let scale_factor = window.scale_factor() as f32;
+    ui_area.offset = (size - shown) / 2.0 / scale_factor;
+    ui_area.size = shown / scale_factor;
+
+    //the side bars run the full height, the top and bottom fit between them
+    let play = play / 2.0;
+    let side = (half.x - play.x).max(0.0);
+    let cap = (half.y - play.y).max(0.0);
+    for(bar, mut sprite, mut transform) in bar_query.iter_mut(){
+        let (size, x, y) = match bar.edge{
+            Edge::Left => (Vec2::new(side, half.y * 2.0), -(play.x + side / 2.0), 0.0),
+            Edge::Right => (Vec2::new(side, half.y * 2.0), play.x + side / 2.0, 0.0),
+            Edge::Bottom => (Vec2::new(play.x * 2.0, cap), 0.0, -(play.y + cap / 2.0)),
+            Edge::Top => (Vec2::new(play.x * 2.0, cap), 0.0, play.y + cap / 2.0),
+        };
+        sprite.size = size;
+        transform.translation = Vec3::new(x, y, BAR_Z);
+    }
+}
+
+//new ui roots are fitted as they come, the rest when the area moves.
+fn fit_ui(
+    ui_area: Res<UiArea>,
+    mut ui_query: Query<&mut Style, With<PlayArea>>,
+    ){
+    let position = Rect{
+        left: Val::Px(ui_area.offset.x),
+        bottom: Val::Px(ui_area.offset.y),
+        ..Default::default()
+    };
+    let size = Size::new(Val::Px(ui_area.size.x), Val::Px(ui_area.size.y));
+    for mut style in ui_query.iter_mut(){
+        //only touched when it moves, a changed style lays the ui out again
+        if style.position != position || style.size != size{
+            style.position_type = PositionType::Absolute;
+            style.position = position;
+            style.size = size;
+        }
+    }
+}
@@ -4,43 +4,60 @@ use bevy::core::FixedTimestep;
 
 use rand::prelude::*;
 
-use crate::{SCALE_UP, Materials,  Direction,  Velocity, Gravity, 
+use crate::{SCALE_UP, Direction,  Velocity, Gravity, Body, Contact, 
     Player, StrikeBox, PlayerAction, Energy, Attacking, 
     Enemy, Proximity, WinSize, ENEMYSPEEDFAST, ENEMYSPEEDMED, ENEMYSPEEDSLOW, 
-    ENEMYSPEEDSTOP};
+    ENEMYSPEEDSTOP, Skeleton, Mage, Element, Difficulty, Knockback, WorldScroll,
+    AppState, RestartRun, while_playing, SIMULATION, TIMESTEP};
+use crate::registry::AssetRegistry;
+use crate::tuning::Tuning;
+use crate::projectiles::spawn_projectile;
+use crate::sound::{PlayCue, Cue};
+use crate::status::{StatusEffects, slowed};
+use crate::telegraph::{Telegraphs, start_telegraph};
+
+//mages back off inside KEEP_AWAY and close in past CAST_RANGE
+const MAGE_KEEP_AWAY: f32 = 250.0;
+const MAGE_CAST_RANGE: f32 = 450.0;
 
 
 pub struct EnemiesPlugin;
 impl Plugin for EnemiesPlugin{
     fn build(&self, app: &mut AppBuilder){
         app
-            .add_startup_stage(
-                "enemy", 
-                SystemStage::single(enemy_spawn.system(),)
-                )
             .add_system_set(
                 SystemSet::new()
-                    .with_run_criteria(FixedTimestep::step(1.0))
+                    .with_run_criteria(FixedTimestep::step(1.0).chain(while_playing.system()))
                     .with_system(ai_enemy.system()),
                        )
-            .add_system(scroll_enemy.system())
-            .add_system(animate_enemy.system())
-            .add_system(near_player.system())
-            .add_system(control_enemy.system())
-            .add_system(attacking_enemy.system());
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(animate_enemy.system())
+                    .with_system(animate_mage.system())
+                    .with_system(near_player.system())
+                    .with_system(attacking_enemy.system())
+                       )
+            .add_system_to_stage(SIMULATION, scroll_enemy.system().label("scroll_world").after("update_scroll"))
+            .add_system_to_stage(SIMULATION, control_enemy.system())
+            .add_system_to_stage(SIMULATION, ai_mage.system())
+            .add_system(clear_enemies.system());
     }
 }
 
 
-fn enemy_spawn(
-    mut commands: Commands,
-    materials: Res<Materials>,
-    ){
+//enemies are placed by the level file. see level.rs
+pub(crate) fn spawn_skeleton(
+    commands: &mut Commands,
+    registry: &AssetRegistry,
+    tuning: &Tuning,
+    x: f32,
+    ) -> Entity{
+    let archetype = tuning.archetype("skeleton");
     commands
         .spawn_bundle(SpriteSheetBundle{
-            texture_atlas: materials.skely_one_sprite.clone(),
+            texture_atlas: registry.atlas("skeleton"),
             transform: Transform{
-                translation: Vec3::new(700.0, 0.0, 1.8),
+                translation: Vec3::new(x, 0.0, 1.8),
                 scale: Vec3::new(SCALE_UP, SCALE_UP, 0.0),
                 ..Default::default()
             },
@@ -51,15 +68,22 @@ fn enemy_spawn(
         direction: Direction::Right,
         vel_mod: ENEMYSPEEDSTOP,
     })
-    .insert(Timer::from_seconds(0.1, true))
+    .insert(Timer::from_seconds(tuning.skeleton_animation.frame_time, true))
     .insert(Gravity{
         falling: false,
     })
+    .insert(Contact{
+        ground: false,
+        side: None,
+        platform: None,
+    })
+    .insert(archetype.body.body())
     .insert(Velocity{
-        velocity: Vec3::new(1.0, 0.0, 0.0),
+        velocity: Vec3::new(0.0, 0.0, 0.0),
     })
     .insert(Energy{
-        power: 25, //skelly is low energy.
+        power: archetype.power,
+        max: archetype.power,
     })
     .insert(Proximity{
         near_player: false,
@@ -72,27 +96,84 @@ fn enemy_spawn(
         w: 115.0,
         attack_h: 40.0,
         attack_w: 130.0,
-    });
+    })
+    .insert(StatusEffects::new())
+    .insert(Skeleton)
+    .id()
+}
 
+pub(crate) fn spawn_mage(
+    commands: &mut Commands,
+    tuning: &Tuning,
+    texture_atlas: Handle<TextureAtlas>,
+    element: Element,
+    x: f32,
+    ) -> Entity{
+    let archetype = tuning.archetype("mage");
+    commands
+        .spawn_bundle(SpriteSheetBundle{
+            texture_atlas,
+            transform: Transform{
+                translation: Vec3::new(x, 0.0, 1.8),
+                scale: Vec3::new(SCALE_UP, SCALE_UP, 0.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    .insert(Enemy{
+        action: PlayerAction::Stand,
+        direction: Direction::Left,
+        vel_mod: ENEMYSPEEDSTOP,
+    })
+    .insert(Mage{
+        element,
+        cast_timer: Timer::from_seconds(2.5, false),
+    })
+    .insert(Timer::from_seconds(tuning.mage_animation.frame_time, true))
+    .insert(Gravity{
+        falling: false,
+    })
+    .insert(Contact{
+        ground: false,
+        side: None,
+        platform: None,
+    })
+    .insert(archetype.body.body())
+    .insert(Velocity{
+        velocity: Vec3::new(0.0, 0.0, 0.0),
+    })
+    .insert(Energy{
+        power: archetype.power,
+        max: archetype.power,
+    })
+    .insert(Proximity{
+        near_player: false,
+    })
+    .insert(Attacking{
+        attack: false,
+    })
+    .insert(StrikeBox{
+        h: 40.0,
+        w: 70.0,
+        attack_h: 40.0,
+        attack_w: 70.0,
+    })
+    .insert(StatusEffects::new())
+    .id()
 }
 
 fn near_player(
     window: Res<WinSize>,
-    mut q: QuerySet<(
-        Query<&mut Transform, With<Enemy>>,
-        Query<&mut Transform, With<Player>>,
-        Query<&mut Proximity, With<Enemy>>,
-        )>,
+    player_query: Query<&Transform, With<Player>>,
+    mut enemy_query: Query<(&Transform, &mut Proximity), With<Enemy>>,
     ){
-        let mut enemy_x: f32 = 0.0;
         let mut player_x: f32 = 0.0;
-        for transform in q.q0_mut().iter_mut(){
-            enemy_x = transform.translation.x; 
-        }
-        for transform in q.q1_mut().iter_mut(){
+        for transform in player_query.iter(){
             player_x = transform.translation.x + (window.w / 2.0);
         }
-        for mut proximity in q.q2_mut().iter_mut(){
+        //each enemy checks itself against the screen edges
+        for (transform, mut proximity) in enemy_query.iter_mut(){
+            let enemy_x = transform.translation.x; 
             if enemy_x < player_x && enemy_x > -player_x{
                 proximity.near_player = true;
             }else{
@@ -103,51 +184,31 @@ fn near_player(
 
 fn animate_enemy(
     time: Res<Time>,
-    texture_atlases: Res<Assets<TextureAtlas>>,
-    mut query: Query<(
-        &mut Timer, &mut TextureAtlasSprite, 
-        &Handle<TextureAtlas>, &mut Enemy, &mut Transform,
-             )>,
-             ){
-
-    for(mut timer, mut sprite, texture_atlas_handle, mut enemy, transform) in query.iter_mut(){
+    tuning: Res<Tuning>,
+    mut query: Query<(&mut Timer, &mut TextureAtlasSprite, &mut Enemy), With<Skeleton>>,
+    ){
+    let frames = &tuning.skeleton_animation;
+    for(mut timer, mut sprite, mut enemy) in query.iter_mut(){
         timer.tick(time.delta());
         if timer.finished(){
-            let texture_atlas = texture_atlases.get(texture_atlas_handle).unwrap();
-
             match enemy.action{
                 PlayerAction::Walk => {
                     enemy.vel_mod = ENEMYSPEEDSLOW;
-                    match sprite.index{
-                        18 => sprite.index = 19,
-                        19 => sprite.index = 20,
-                        20 => sprite.index = 21,
-                        _ => sprite.index = 18,
-                    }
+                    sprite.index = frames.walk.next(sprite.index);
                 }
                 
                 PlayerAction::Charge =>{
                     enemy.vel_mod = ENEMYSPEEDFAST;
-                    match sprite.index{
-                        14 => sprite.index = 15,
-                        15 => sprite.index = 16,
-                        16 => sprite.index = 17,
-                        _ => sprite.index = 14,
-                    } 
-                    if sprite.index == 17{
+                    sprite.index = frames.charge.next(sprite.index);
+                    if sprite.index == frames.charge.last{
                         enemy.vel_mod = ENEMYSPEEDSLOW;
                         enemy.action = PlayerAction::Walk;
                     }
                 }
 
                 PlayerAction::Chop =>{
-                    match sprite.index{
-                        27 => sprite.index = 28,
-                        28 => sprite.index = 29,
-                        29 => sprite.index = 30, 
-                        _ => sprite.index = 27,
-                    } 
-                    if sprite.index == 30{
+                    sprite.index = frames.chop.next(sprite.index);
+                    if sprite.index == frames.chop.last{
                         enemy.vel_mod = ENEMYSPEEDSTOP;
                         enemy.action = PlayerAction::Stand;
                     }
@@ -155,49 +216,72 @@ fn animate_enemy(
 
                 PlayerAction::Block =>{
                     enemy.vel_mod = ENEMYSPEEDSTOP;
-                    match sprite.index{
-                        23 => sprite.index = 24,
-                        24 => sprite.index = 25,
-                        25 => sprite.index = 26,
-                        _ => sprite.index = 23,
-                    }
-                    if sprite.index == 26{
+                    sprite.index = frames.block.next(sprite.index);
+                    if sprite.index == frames.block.last{
                         enemy.vel_mod = ENEMYSPEEDSTOP;
                         enemy.action = PlayerAction::Stand;
                     }
                 }
-                _ => sprite.index = 18,
+
+                PlayerAction::WindUp =>{
+                    //hold the pose while the telegraph flashes
+                    enemy.vel_mod = ENEMYSPEEDSTOP;
+                }
+                _ => sprite.index = frames.stand,
             }
         }
     }
 }
 
 fn scroll_enemy(
-    time: Res<Time>,
-    mut enemy_query: Query<(&mut Enemy, &mut Transform, &Velocity, &Proximity)>,
+    scroll: Res<WorldScroll>,
+    mut enemy_query: Query<(&mut Enemy, &mut Transform, &mut Body, &Proximity, 
+                            Option<&Mage>, Option<&StatusEffects>)>,
     mut player_query: Query<(&Player, &StrikeBox)>,
         
     ){
     if let Ok((player, strike_box)) = player_query.single_mut(){
-        //what direction is player facing? If player moves, the whole 
-        //game moves with him.  So enemy must scroll with him.
-        let dir = match player.direction{
-            Direction::Right => -1.0,
-            Direction::Left => 1.0,
-            _ => 0.0,
-        };
-        let mut vel = dir * player.vel_mod;
-        for (mut enemy, mut transform, enemy_velocity, proximity) in enemy_query.iter_mut(){
-
+        for (mut enemy, mut transform, mut body, proximity, mage, status) in enemy_query.iter_mut(){
+            //the enemy's own walking is a velocity. physics.rs moves it.
+            let mut target = 0.0;
+            let enemy_speed = slowed(enemy.vel_mod, status);
+
+            //stunned and knocked down enemies just ride the scroll.
+            let can_move = match status{
+                Some(status) => !status.locks_input(),
+                None => true,
+            };
+
+            //mages keep their distance instead of closing in.
+            if can_move && proximity.near_player && mage.is_some(){
+                let distance = transform.translation.x - player.current_x;
+                let moving = enemy.action == PlayerAction::Walk 
+                    || enemy.action == PlayerAction::Stand;
+                if moving && distance.abs() < MAGE_KEEP_AWAY{
+                    target = distance.signum() * slowed(ENEMYSPEEDSLOW, status);
+                    enemy.action = PlayerAction::Walk;
+                }else if moving && distance.abs() > MAGE_CAST_RANGE{
+                    target = -distance.signum() * slowed(ENEMYSPEEDSLOW, status);
+                    enemy.action = PlayerAction::Walk;
+                }else if enemy.action == PlayerAction::Walk{
+                    enemy.action = PlayerAction::Stand;
+                }
+                //always face the player
+                if distance > 0.0{
+                    transform.rotation = Quat::from_rotation_y(std::f32::consts::PI);
+                }else{
+                    transform.rotation = Quat::default();
+                }
+            }
             //once enemy is on screen, the enemy closes in on player and scrolling
             //stops. 
-            if proximity.near_player{
+            else if can_move && proximity.near_player{
                 if transform.translation.x > player.current_x + (strike_box.w / 2.0){
-                    vel += -1.0 * enemy.vel_mod;
+                    target = -1.0 * enemy_speed;
                     //flip sprite 
                     transform.rotation = Quat::from_rotation_y(std::f32::consts::PI);
                 }else if transform.translation.x < player.current_x - (strike_box.w / 2.0){
-                    vel += 1.0 * enemy.vel_mod;
+                    target = 1.0 * enemy_speed;
                     //flip sprite
                     transform.rotation = Quat::default();
                 }
@@ -207,24 +291,28 @@ fn scroll_enemy(
                     enemy.action = PlayerAction::Walk;
                 }
 
-            }else{
-                //once enemy is not on screen, player movement controls enemy
-                vel = dir * player.vel_mod;
             }
+            body.target_vx = target;
 
+            //If player moves, the whole game moves with him.  
+            //So enemy must scroll with him.
             //multiplyer is the z value of the background. The further back
             //into the background, the slower the multiplier.
             let multiplier = transform.translation.z;
             transform.translation.x  += 
-                (vel * multiplier) * enemy_velocity.velocity.x * time.delta_seconds();
+                (scroll.speed * multiplier) * TIMESTEP;
 
         }
     }
 }
 
 fn ai_enemy(
-    mut enemy_query: Query<(&mut Enemy, &Transform, &Energy, &Proximity)>,
-    mut player_query: Query<(&Player)>,
+    mut commands: Commands,
+    telegraphs: Res<Telegraphs>,
+    difficulty: Res<Difficulty>,
+    mut enemy_query: Query<(Entity, &mut Enemy, &Transform, &Proximity, &StatusEffects), 
+                           With<Skeleton>>,
+    player_query: Query<&Player>,
     ){
     //create the random gen
 
@@ -232,12 +320,12 @@ fn ai_enemy(
     let mut rng = thread_rng();
 
 
-    if let Ok(player) = player_query.single_mut(){
-        for(mut enemy, enemy_transform, energy, proximity) in enemy_query.iter_mut(){
+    if let Ok(player) = player_query.single(){
+        for(entity, mut enemy, enemy_transform, proximity, status) in enemy_query.iter_mut(){
             //don't do anything unless we are on screen.
             //and if we are being hit by player (Bumped) take no action.
-            if proximity.near_player{
-                let mut distance = (player.current_x - enemy_transform.translation.x).abs();
+            if proximity.near_player && !status.locks_input(){
+                let distance = (player.current_x - enemy_transform.translation.x).abs();
                 //possible range between 400 - 57 so divide by 50 to get 8 levels
                 //of proximity. 8 will be farther away than 0.
                 let imparative = distance as i32 / 50;
@@ -306,6 +394,88 @@ fn ai_enemy(
                             //nothing
                             }
                     }
+                    //attacks get a wind-up first so the player can react
+                    if enemy.action == PlayerAction::Charge || enemy.action == PlayerAction::Chop{
+                        let next = enemy.action;
+                        start_telegraph(&mut commands, &telegraphs, &difficulty, 
+                                        entity, &mut enemy, next, false);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn ai_mage(
+    mut commands: Commands,
+    telegraphs: Res<Telegraphs>,
+    difficulty: Res<Difficulty>,
+    mut enemy_query: Query<(Entity, &mut Enemy, &mut Mage, &Transform, &Proximity, &StatusEffects)>,
+    player_query: Query<&Player>,
+    ){
+    if let Ok(player) = player_query.single(){
+        for(entity, mut enemy, mut mage, transform, proximity, status) in enemy_query.iter_mut(){
+            mage.cast_timer.tick(std::time::Duration::from_secs_f32(TIMESTEP));
+            if !proximity.near_player || status.locks_input(){
+                continue;
+            }
+            //cast whenever the spell is ready and the player is in range.
+            let distance = (player.current_x - transform.translation.x).abs();
+            if mage.cast_timer.finished() && distance <= MAGE_CAST_RANGE 
+                && enemy.action == PlayerAction::Stand{
+                start_telegraph(&mut commands, &telegraphs, &difficulty,
+                                entity, &mut enemy, PlayerAction::Chop, true);
+                mage.cast_timer.reset();
+            }
+        }
+    }
+}
+
+fn animate_mage(
+    mut commands: Commands,
+    mut cue_events: EventWriter<PlayCue>,
+    time: Res<Time>,
+    registry: Res<AssetRegistry>,
+    tuning: Res<Tuning>,
+    mut query: Query<(
+        &mut Timer, &mut TextureAtlasSprite, &mut Enemy, &Mage, &Transform,
+             )>,
+             ){
+    let frames = &tuning.mage_animation;
+    for(mut timer, mut sprite, mut enemy, mage, transform) in query.iter_mut(){
+        timer.tick(time.delta());
+        if timer.finished(){
+            match enemy.action{
+                PlayerAction::Walk => {
+                    enemy.vel_mod = ENEMYSPEEDSLOW;
+                    sprite.index = frames.walk.next(sprite.index);
+                }
+
+                PlayerAction::Chop =>{
+                    enemy.vel_mod = ENEMYSPEEDSTOP;
+                    sprite.index = frames.cast.next(sprite.index);
+                    if sprite.index == frames.release{
+                        let dir = if transform.rotation == Quat::default(){
+                            1.0
+                        }else{
+                            -1.0
+                        };
+                        let position = transform.translation 
+                            + Vec3::new(dir * 40.0, 10.0, 0.0);
+                        spawn_projectile(&mut commands, &registry, mage.element, position, dir);
+                        cue_events.send(PlayCue{
+                            cue: Cue::Cast,
+                            from_x: Some(transform.translation.x),
+                        });
+                    }
+                    if sprite.index == frames.cast.last{
+                        enemy.action = PlayerAction::Stand;
+                    }
+                }
+
+                _ => {
+                    enemy.vel_mod = ENEMYSPEEDSTOP;
+                    sprite.index = frames.idle.next(sprite.index);
                 }
             }
         }
@@ -325,31 +495,24 @@ fn attacking_enemy(
 }
 
 fn control_enemy(
-    time: Res<Time>,
-    mut enemy_query: Query<(&mut Enemy, &mut Transform, 
-                      &mut Velocity, &mut Gravity)>,
-    mut player_query: Query<(&Player, &mut Attacking)>,
+    mut knockback_events: EventWriter<Knockback>,
+    mut enemy_query: Query<(Entity, &mut Enemy)>,
+    mut player_query: Query<(&Transform, &mut Attacking), With<Player>>,
     
     ){
-    for(mut enemy, mut transform, mut enemy_velocity, 
-       mut enemy_gravity) in enemy_query.iter_mut(){
-
-        
-
-        let delta_seconds = f32::min(0.3, time.delta_seconds());
+    for(entity, mut enemy) in enemy_query.iter_mut(){
 
         match enemy.action{
             PlayerAction::Bumped => {
-                if let Ok((player, mut attacking)) = player_query.single_mut(){
+                if let Ok((player_tf, mut attacking)) = player_query.single_mut(){
                     attacking.attack = false; //turn off attack
-                    transform.translation.y += 30.0;//get enemy off player 
-                    enemy_velocity.velocity.y = 30.0;
-                    enemy_gravity.falling = true;
-                    if player.direction == Direction::Left{
-                        transform.translation.x -= 40.0; //get it off platform
-                    }else{
-                        transform.translation.x += 40.0; //get it off platform
-                    }
+                    //knocked away from the player
+                    knockback_events.send(Knockback{
+                        target: entity,
+                        from_x: player_tf.translation.x,
+                        force: 350.0,
+                        stun: 0.4,
+                    });
                 }
                 //turn off Bumped 
                 enemy.action = PlayerAction::Stand;
@@ -360,3 +523,16 @@ fn control_enemy(
         }
     }
 }
+
+//the boss too, the level puts them all back.
+fn clear_enemies(
+    mut commands: Commands,
+    mut restart_events: EventReader<RestartRun>,
+    query: Query<Entity, With<Enemy>>,
+    ){
+    for _ in restart_events.iter(){
+        for entity in query.iter(){
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use rand::prelude::*;
+
+use crate::{Enemy, Energy, StrikeBox, Damage, DamageKind, WorldScroll,
+    AppState, RestartRun, SIMULATION, TIMESTEP};
+use crate::registry::AssetRegistry;
+use crate::boss::Boss;
+
+const BAR_WIDTH: f32 = 60.0;
+const BAR_HEIGHT: f32 = 6.0;
+const BAR_GAP: f32 = 12.0; //between the top of the strike box and the bar
+const BAR_FADE: f32 = 0.4; //seconds to fade all the way in or out
+const NUMBER_LIFE: f32 = 0.9; //seconds before a number is gone
+const NUMBER_RISE: f32 = 70.0; //px/s
+const NUMBER_SPREAD: f32 = 15.0; //so numbers from quick hits don't stack
+const OVERLAY_Z: f32 = 5.0; //in front of the bodies
+
+//a small bar over an enemy once it has been hurt. the boss has his
+//own in the hud. the fill is a child so it moves with the back.
+struct HealthBar{
+    target: Entity,
+    fill: Entity,
+    back_material: Handle<ColorMaterial>,
+    fill_material: Handle<ColorMaterial>,
+    alpha: f32,
+}
+
+//rises from the hit and fades. depth is the z of what was hit, so it
+//scrolls at the same speed.
+struct FloatingNumber{
+    timer: Timer,
+    depth: f32,
+    color: Color,
+}
+
+pub struct FloatingPlugin;
+impl Plugin for FloatingPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_system(add_bars.system())
+            .add_system(spawn_numbers.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(fade_bars.system())
+                    .with_system(fade_numbers.system())
+                       )
+            .add_system(clear_numbers.system())
+            .add_system_to_stage(SIMULATION,
+                scroll_numbers.system().label("scroll_world").after("update_scroll"))
+            .add_system_to_stage(CoreStage::PostUpdate,
+                follow_targets.system()
+                    .after("interpolate_positions")
+                    .before(TransformSystem::TransformPropagate));
+    }
+}
+
+fn add_bars(
+    mut commands: Commands,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    enemy_query: Query<(Entity, &Energy), (With<Enemy>, Without<Boss>)>,
+    bar_query: Query<&HealthBar>,
+    ){
+    for(entity, energy) in enemy_query.iter(){
+        if energy.power >= energy.max || bar_query.iter().any(|bar| bar.target == entity){
+            continue;
+        }
+        //each bar gets its own materials so it can fade on its own.
+        //starts see-through and fades in.
+        let back_material = color_materials.add(Color::rgba(0.1, 0.1, 0.1, 0.0).into());
+        let fill_material = color_materials.add(Color::rgba(0.8, 0.15, 0.15, 0.0).into());
+        let fill = commands
+            .spawn_bundle(SpriteBundle{
+                material: fill_material.clone(),
+                sprite: Sprite::new(Vec2::new(BAR_WIDTH, BAR_HEIGHT)),
+                transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                ..Default::default()
+            })
+            .id();
+        commands
+            .spawn_bundle(SpriteBundle{
+                material: back_material.clone(),
+                sprite: Sprite::new(Vec2::new(BAR_WIDTH + 2.0, BAR_HEIGHT + 2.0)),
+                ..Default::default()
+            })
+            .insert(HealthBar{
+                target: entity,
+                fill,
+                back_material,
+                fill_material,
+                alpha: 0.0,
+            })
+            .push_children(&[fill]);
+    }
+}
+
+//shown while the enemy is hurt, faded out at full health. gone with it.
+fn fade_bars(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    mut bar_query: Query<(Entity, &mut HealthBar)>,
+    mut fill_query: Query<(&mut Sprite, &mut Transform)>,
+    energy_query: Query<&Energy>,
+    ){
+    let step = time.delta_seconds() / BAR_FADE;
+    for(entity, mut bar) in bar_query.iter_mut(){
+        let energy = match energy_query.get(bar.target){
+            Ok(energy) => energy,
+            Err(_) => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        };
+
+        let target = if energy.power < energy.max{ 1.0 }else{ 0.0 };
+        let diff = target - bar.alpha;
+        bar.alpha = if diff.abs() <= step{ target }else{ bar.alpha + step * diff.signum() };
+        for handle in [&bar.back_material, &bar.fill_material].iter(){
+            if let Some(material) = color_materials.get_mut(*handle){
+                material.color.set_a(bar.alpha);
+            }
+        }
+
+        //the fill shrinks toward the left end
+        if let Ok((mut sprite, mut transform)) = fill_query.get_mut(bar.fill){
+            let share = (energy.power as f32 / energy.max as f32).max(0.0).min(1.0);
+            sprite.size.x = BAR_WIDTH * share;
+            transform.translation.x = -BAR_WIDTH * (1.0 - share) / 2.0;
+        }
+    }
+}
+
+//after interpolation so the bar sits on the enemy as drawn.
+fn follow_targets(
+    mut bar_query: Query<(&HealthBar, &mut Transform)>,
+    target_query: Query<(&Transform, &StrikeBox), Without<HealthBar>>,
+    ){
+    for(bar, mut transform) in bar_query.iter_mut(){
+        if let Ok((target_tf, strike_box)) = target_query.get(bar.target){
+            transform.translation = Vec3::new(
+                target_tf.translation.x,
+                target_tf.translation.y + strike_box.w / 2.0 + BAR_GAP,
+                OVERLAY_Z,
+                );
+        }
+    }
+}
+
+fn number_style(kind: DamageKind) -> (Color, f32){
+    match kind{
+        DamageKind::Normal => (Color::WHITE, 22.0),
+        DamageKind::Blocked => (Color::rgb(0.6, 0.65, 0.75), 16.0),
+        DamageKind::Critical => (Color::rgb(1.0, 0.85, 0.1), 32.0),
+        DamageKind::Status => (Color::rgb(1.0, 0.45, 0.1), 18.0),
+    }
+}
+
+fn spawn_numbers(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    mut damage_events: EventReader<Damage>,
+    query: Query<(&Transform, &StrikeBox), With<Enemy>>,
+    ){
+    let mut rng = thread_rng();
+    for damage in damage_events.iter(){
+        let (transform, strike_box) = match query.get(damage.target){
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        let (color, size) = number_style(damage.kind);
+        let value = match damage.kind{
+            DamageKind::Critical => format!("{}!", damage.amount),
+            DamageKind::Blocked if damage.amount <= 0 => "blocked".to_string(),
+            _ => damage.amount.to_string(),
+        };
+        commands
+            .spawn_bundle(Text2dBundle{
+                text: Text::with_section(
+                    value,
+                    TextStyle{
+                        font: registry.font("hud"),
+                        font_size: size,
+                        color,
+                    },
+                    TextAlignment{
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    },
+                    ),
+                transform: Transform::from_xyz(
+                    transform.translation.x + rng.gen_range(-NUMBER_SPREAD..=NUMBER_SPREAD),
+                    transform.translation.y + strike_box.w / 2.0,
+                    OVERLAY_Z,
+                    ),
+                ..Default::default()
+            })
+            .insert(FloatingNumber{
+                timer: Timer::from_seconds(NUMBER_LIFE, false),
+                depth: transform.translation.z,
+                color,
+            });
+    }
+}
+
+fn scroll_numbers(
+    scroll: Res<WorldScroll>,
+    mut query: Query<(&FloatingNumber, &mut Transform)>,
+    ){
+    for(number, mut transform) in query.iter_mut(){
+        transform.translation.x += scroll.speed * number.depth * TIMESTEP;
+    }
+}
+
+fn fade_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut FloatingNumber, &mut Transform, &mut Text)>,
+    ){
+    for(entity, mut number, mut transform, mut text) in query.iter_mut(){
+        number.timer.tick(time.delta());
+        if number.timer.finished(){
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation.y += NUMBER_RISE * time.delta_seconds();
+        let left = 1.0 - number.timer.percent();
+        let mut color = number.color;
+        color.set_a(left);
+        text.sections[0].style.color = color;
+    }
+}
+
+//the bars go on their own once their enemies are cleared.
+fn clear_numbers(
+    mut commands: Commands,
+    mut restart_events: EventReader<RestartRun>,
+    query: Query<Entity, With<FloatingNumber>>,
+    ){
+    for _ in restart_events.iter(){
+        for entity in query.iter(){
+            commands.entity(entity).despawn();
+        }
+    }
+}
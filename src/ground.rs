@@ -0,0 +1,198 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{WinSize, WorldScroll, Platform, Velocity, Gravity, Player, StrikeBox,
+    Contact, Damage, DamageKind, Energy, SIMULATION, TIMESTEP};
+use crate::registry::AssetRegistry;
+use crate::level::{GROUND_TOP, LastCheckpoint};
+use crate::boss::Boss;
+
+const TILE_W: f32 = 32.0;
+const TILE_H: f32 = 32.0;
+const GROUND_Z: f32 = 1.9; //same depth as the platforms so they scroll together
+const FALL_OUT: f32 = 100.0; //this far below the screen is gone for good
+const PIT_DAMAGE: i32 = 20;
+const RESPAWN_MARGIN: f32 = 60.0; //how far back from the pit's edge
+
+//a gap in the ground. x is where it starts in the world, like the platforms.
+#[derive(Deserialize, Clone)]
+pub(crate) struct PitSpec{
+    pub(crate) x: f32,
+    pub(crate) w: f32,
+    #[serde(default)]
+    pub(crate) lethal: bool, //no coming back from this one
+}
+
+//the pits in the current level, and where the level's x 0.0 has
+//scrolled to on screen.
+pub(crate) struct Pits{
+    pub(crate) spans: Vec<PitSpec>,
+    pub(crate) origin: f32,
+}
+
+impl Pits{
+    fn at(&self, world_x: f32) -> Option<&PitSpec>{
+        self.spans.iter().find(|pit| world_x >= pit.x && world_x < pit.x + pit.w)
+    }
+}
+
+//moves the world so level_x ends up under player_x. it happens on
+//the next scroll, all at once. see physics.rs
+pub(crate) fn warp_to(scroll: &mut WorldScroll, pits: &Pits, player_x: f32, level_x: f32){
+    scroll.warp = (player_x - pits.origin - level_x) / GROUND_Z;
+}
+
+//the ground is a row of tiles a little wider than the screen. tiles
+//that scroll off one side come back on the other, so it never ends.
+pub(crate) struct GroundTile{
+    hole: bool,
+}
+
+pub struct GroundPlugin;
+impl Plugin for GroundPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .insert_resource(Pits{
+                spans: Vec::new(),
+                origin: 0.0,
+            })
+            .add_startup_stage(
+                "ground",
+                SystemStage::single(ground_spawn.system()),
+                )
+            .add_system_to_stage(SIMULATION,
+                scroll_ground.system().label("scroll_world").after("update_scroll"))
+            .add_system_to_stage(SIMULATION, fall_out.system().label("deal_damage").after("sweep_platforms"));
+    }
+}
+
+fn tile_count(window: &WinSize) -> usize{
+    (window.w / TILE_W).ceil() as usize + 2
+}
+
+fn ground_spawn(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    window: Res<WinSize>,
+    ){
+    let count = tile_count(&window);
+    let left = -(count as f32 * TILE_W) / 2.0 + TILE_W / 2.0;
+    let y = -(window.h / 2.0) + GROUND_TOP - TILE_H / 2.0;
+    for i in 0..count{
+        commands
+            .spawn_bundle(SpriteBundle{
+                material: registry.material("ground"),
+                sprite: Sprite::new(Vec2::new(TILE_W, TILE_H)),
+                transform: Transform{
+                    translation: Vec3::new(left + i as f32 * TILE_W, y, GROUND_Z),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(GroundTile{
+                hole: false,
+            })
+            .insert(Platform)
+            .insert(Velocity{
+                velocity: Vec3::new(1.0, 0.0, 0.0),
+            });
+    }
+}
+
+//scrolls like a platform, then wraps and opens or closes pits.
+//a tile over a pit is hidden and stops being a platform.
+fn scroll_ground(
+    mut commands: Commands,
+    scroll: Res<WorldScroll>,
+    window: Res<WinSize>,
+    mut pits: ResMut<Pits>,
+    mut query: Query<(Entity, &mut GroundTile, &mut Transform, &mut Visible, &Velocity)>,
+    ){
+    let shift = scroll.speed * GROUND_Z * TIMESTEP;
+    pits.origin += shift;
+    let span = tile_count(&window) as f32 * TILE_W;
+
+    for(entity, mut tile, mut transform, mut visible, velocity) in query.iter_mut(){
+        transform.translation.x += shift * velocity.velocity.x;
+        if transform.translation.x < -span / 2.0{
+            transform.translation.x += span;
+        }else if transform.translation.x >= span / 2.0{
+            transform.translation.x -= span;
+        }
+
+        let hole = pits.at(transform.translation.x - pits.origin).is_some();
+        if hole != tile.hole{
+            tile.hole = hole;
+            visible.is_visible = !hole;
+            if hole{
+                commands.entity(entity).remove::<Platform>();
+            }else{
+                commands.entity(entity).insert(Platform);
+            }
+        }
+    }
+}
+
+//enemies that fall out of the world are gone. the player gets hurt
+//and put back at the last checkpoint, or before the pit he fell in
+//if he hasn't reached one. the boss
+//going down a pit counts as beating him, so the arena still opens.
+fn fall_out(
+    mut commands: Commands,
+    mut damage_events: EventWriter<Damage>,
+    mut scroll: ResMut<WorldScroll>,
+    pits: Res<Pits>,
+    last_checkpoint: Res<LastCheckpoint>,
+    window: Res<WinSize>,
+    mut player_query: Query<(Entity, &mut Transform, &mut Velocity, &mut Gravity, &StrikeBox,
+                             &Energy), With<Player>>,
+    mut boss_query: Query<(&mut Transform, &mut Velocity, &mut Energy), (With<Boss>, Without<Player>)>,
+    body_query: Query<(Entity, &Transform), (With<Contact>, Without<Player>, Without<Boss>)>,
+    ){
+    let screen_bottom = -(window.h / 2.0);
+
+    for(entity, transform) in body_query.iter(){
+        if transform.translation.y < screen_bottom - FALL_OUT{
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    //held just out of sight while boss_defeat plays him out
+    for(mut transform, mut velocity, mut energy) in boss_query.iter_mut(){
+        if transform.translation.y < screen_bottom - FALL_OUT{
+            transform.translation.y = screen_bottom - FALL_OUT;
+            velocity.velocity = Vec3::ZERO;
+            energy.power = 0;
+        }
+    }
+
+    if let Ok((entity, mut transform, mut velocity, mut gravity, strike_box, energy)) = player_query.single_mut(){
+        if transform.translation.y >= screen_bottom - FALL_OUT{
+            return;
+        }
+
+        //the last pit that starts behind him is the one he fell in
+        let world_x = transform.translation.x - pits.origin;
+        let pit = pits.spans.iter()
+            .filter(|pit| pit.x <= world_x)
+            .max_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+        let lethal = pit.map(|pit| pit.lethal).unwrap_or(false);
+        damage_events.send(Damage{
+            target: entity,
+            amount: if lethal{ energy.power }else{ PIT_DAMAGE },
+            kind: DamageKind::Normal,
+        });
+        let respawn_x = match (last_checkpoint.x, pit){
+            (Some(x), _) => Some(x),
+            (None, Some(pit)) => Some(pit.x - RESPAWN_MARGIN),
+            (None, None) => None,
+        };
+        if let Some(respawn_x) = respawn_x{
+            warp_to(&mut scroll, &pits, transform.translation.x, respawn_x);
+        }
+
+        transform.translation.y = screen_bottom + GROUND_TOP + strike_box.w / 2.0;
+        velocity.velocity = Vec3::ZERO;
+        gravity.falling = true;
+    }
+}
@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+use bevy::sprite::collide_aabb::collide;
+use serde::Deserialize;
+
+use crate::{Velocity, Player, StrikeBox, Energy, Damage, DamageKind, Knockback, ApplyStatus,
+    StatusKind, WorldScroll, RestartRun, SIMULATION, TIMESTEP};
+use crate::registry::AssetRegistry;
+
+const HIT_COOLDOWN: f32 = 1.0; //one touch is one hit
+const HAZARD_Z: f32 = 1.9; //same depth as the platforms
+const BURN_TIME: f32 = 3.0;
+
+//what a hazard does. picked per hazard in the level data.
+#[derive(Deserialize, Clone)]
+pub(crate) enum HazardKind{
+    Spikes{damage: i32},
+    Blade{damage: i32, reach: f32, period: f32}, //swings reach either side, period seconds per swing
+    FireJet{damage: i32, on: f32, off: f32}, //burns for on seconds, then rests for off
+    Zone{damage: i32, interval: f32}, //hurts every interval seconds while inside
+}
+
+impl HazardKind{
+    //a trap has to take some time to go round, or it never does. both
+    //loaders turn these away. written so a NaN is turned away too.
+    pub(crate) fn check(&self) -> Result<(), anyhow::Error>{
+        match *self{
+            HazardKind::Blade{period, ..} if !(period > 0.0) =>
+                anyhow::bail!("blade period has to be above 0, not {}", period),
+            HazardKind::FireJet{on, ..} if !(on > 0.0) =>
+                anyhow::bail!("fire jet on time has to be above 0, not {}", on),
+            HazardKind::FireJet{off, ..} if !(off > 0.0) =>
+                anyhow::bail!("fire jet off time has to be above 0, not {}", off),
+            HazardKind::Zone{interval, ..} if !(interval > 0.0) =>
+                anyhow::bail!("zone interval has to be above 0, not {}", interval),
+            _ => Ok(()),
+        }
+    }
+}
+
+//one entry per hazard in the level file. placed like the platforms.
+#[derive(Deserialize)]
+pub(crate) struct HazardSpec{
+    pub(crate) kind: HazardKind,
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) w: f32,
+    pub(crate) h: f32,
+    #[serde(default)]
+    pub(crate) lethal: bool, //takes all the player's energy at once
+}
+
+struct Hazard{
+    kind: HazardKind,
+    lethal: bool,
+    clock: f32, //seconds since spawned, drives the traps
+    offset: f32, //how far a blade has swung from where it hangs
+    active: bool,
+    cooldown: Timer,
+}
+
+pub struct HazardsPlugin;
+impl Plugin for HazardsPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_system_to_stage(SIMULATION,
+                scroll_hazards.system().label("scroll_world").after("update_scroll"))
+            .add_system_to_stage(SIMULATION,
+                cycle_hazards.system().label("cycle_hazards").after("scroll_world"))
+            .add_system_to_stage(SIMULATION, hazard_hits_player.system().label("deal_damage").after("cycle_hazards"))
+            .add_system(clear_hazards.system());
+    }
+}
+
+pub(crate) fn spawn_hazard(
+    commands: &mut Commands,
+    registry: &AssetRegistry,
+    screen_bottom: f32,
+    spec: &HazardSpec,
+    ) -> Entity{
+    let (material, cooldown) = match spec.kind{
+        HazardKind::Spikes{..} => (registry.material("hazard_spikes"), HIT_COOLDOWN),
+        HazardKind::Blade{..} => (registry.material("hazard_blade"), HIT_COOLDOWN),
+        HazardKind::FireJet{..} => (registry.material("hazard_fire"), HIT_COOLDOWN),
+        HazardKind::Zone{interval, ..} => (registry.material("hazard_zone"), interval),
+    };
+    commands
+        .spawn_bundle(SpriteBundle{
+            material,
+            sprite: Sprite::new(Vec2::new(spec.w, spec.h)),
+            transform: Transform{
+                translation: Vec3::new(spec.x, screen_bottom + spec.y, HAZARD_Z),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Hazard{
+            kind: spec.kind.clone(),
+            lethal: spec.lethal,
+            clock: 0.0,
+            offset: 0.0,
+            active: true,
+            cooldown: Timer::from_seconds(cooldown, false),
+        })
+        .insert(Velocity{
+            velocity: Vec3::new(1.0, 0.0, 0.0),
+        })
+        .id()
+}
+
+fn scroll_hazards(
+    scroll: Res<WorldScroll>,
+    mut query: Query<(&mut Transform, &Velocity), With<Hazard>>,
+    ){
+    for(mut transform, velocity) in query.iter_mut(){
+        let multiplier = transform.translation.z;
+        transform.translation +=
+            (scroll.speed * multiplier) * velocity.velocity * TIMESTEP;
+    }
+}
+
+//blades swing on top of the scroll, fire jets flick on and off.
+fn cycle_hazards(
+    mut query: Query<(&mut Hazard, &mut Transform, &mut Visible)>,
+    ){
+    for(mut hazard, mut transform, mut visible) in query.iter_mut(){
+        hazard.clock += TIMESTEP;
+        hazard.cooldown.tick(std::time::Duration::from_secs_f32(TIMESTEP));
+        match hazard.kind{
+            HazardKind::Blade{reach, period, ..} => {
+                let angle = hazard.clock / period * std::f32::consts::PI * 2.0;
+                let offset = reach * angle.sin();
+                transform.translation.x += offset - hazard.offset;
+                hazard.offset = offset;
+            }
+            HazardKind::FireJet{on, off, ..} => {
+                hazard.active = hazard.clock % (on + off) < on;
+                visible.is_visible = hazard.active;
+            }
+            _ => {}
+        }
+    }
+}
+
+//hazards hurt through the same Damage event as everything else.
+fn hazard_hits_player(
+    mut damage_events: EventWriter<Damage>,
+    mut knockback_events: EventWriter<Knockback>,
+    mut status_events: EventWriter<ApplyStatus>,
+    player_query: Query<(Entity, &Transform, &StrikeBox, &Energy), With<Player>>,
+    mut hazard_query: Query<(&mut Hazard, &Transform, &Sprite)>,
+    ){
+    if let Ok((player_entity, player_tf, strike_box, energy)) = player_query.single(){
+        let player_size = Vec2::new(strike_box.h, strike_box.w);
+
+        for(mut hazard, hazard_tf, sprite) in hazard_query.iter_mut(){
+            if !hazard.active || !hazard.cooldown.finished(){
+                continue;
+            }
+            let collision = collide(
+                hazard_tf.translation,
+                sprite.size,
+                player_tf.translation,
+                player_size,
+               );
+            if collision.is_none(){
+                continue;
+            }
+
+            let damage = match hazard.kind{
+                HazardKind::Spikes{damage} => damage,
+                HazardKind::Blade{damage, ..} => damage,
+                HazardKind::FireJet{damage, ..} => damage,
+                HazardKind::Zone{damage, ..} => damage,
+            };
+            damage_events.send(Damage{
+                target: player_entity,
+                amount: if hazard.lethal{ energy.power }else{ damage },
+                kind: DamageKind::Normal,
+            });
+            match hazard.kind{
+                HazardKind::Spikes{..} | HazardKind::Blade{..} => {
+                    knockback_events.send(Knockback{
+                        target: player_entity,
+                        from_x: hazard_tf.translation.x,
+                        force: 250.0,
+                        stun: 0.25,
+                    });
+                }
+                HazardKind::FireJet{..} => {
+                    status_events.send(ApplyStatus{
+                        target: player_entity,
+                        kind: StatusKind::Burn,
+                        duration: BURN_TIME,
+                    });
+                }
+                HazardKind::Zone{..} => {}
+            }
+            hazard.cooldown.reset();
+        }
+    }
+}
+
+fn clear_hazards(
+    mut commands: Commands,
+    mut restart_events: EventReader<RestartRun>,
+    query: Query<Entity, With<Hazard>>,
+    ){
+    for _ in restart_events.iter(){
+        for entity in query.iter(){
+            commands.entity(entity).despawn();
+        }
+    }
+}
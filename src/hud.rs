@@ -0,0 +1,274 @@
+use bevy::prelude::*;
+
+use crate::{Player, Energy};
+use crate::registry::AssetRegistry;
+use crate::display::PlayArea;
+use crate::boss::Boss;
+use crate::night::Night;
+use crate::stamina::Stamina;
+use crate::score::Score;
+
+const BAR_WIDTH: f32 = 220.0;
+const BAR_HEIGHT: f32 = 14.0;
+const BOSS_BAR_WIDTH: f32 = 500.0;
+const BOSS_BAR_HEIGHT: f32 = 16.0;
+const MARGIN: f32 = 12.0;
+const FONT_SIZE: f32 = 22.0;
+
+//everything is pinned to a corner or edge of the play area, so it
+//stays put when the window changes size and off the letterbox bars.
+struct HealthFill;
+struct StaminaFill;
+struct ScoreText;
+struct ComboText;
+struct DawnText;
+struct BossBar; //all the parts, hidden until the boss fight
+struct BossFill;
+
+pub struct HudPlugin;
+impl Plugin for HudPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_startup_stage(
+                "hud",
+                SystemStage::single(hud_spawn.system()),
+                )
+            .add_system(hud_health.system())
+            .add_system(hud_stamina.system())
+            .add_system(hud_score.system())
+            .add_system(hud_combo.system())
+            .add_system(hud_dawn.system())
+            .add_system(hud_boss.system());
+    }
+}
+
+//pinned to the edges given in position.
+fn pinned(position: Rect<Val>) -> Style{
+    Style{
+        position_type: PositionType::Absolute,
+        position,
+        ..Default::default()
+    }
+}
+
+fn text_bundle(registry: &AssetRegistry, style: Style) -> TextBundle{
+    TextBundle{
+        style,
+        text: Text::with_section(
+            "",
+            TextStyle{
+                font: registry.font("hud"),
+                font_size: FONT_SIZE,
+                color: Color::WHITE,
+            },
+            Default::default(),
+            ),
+        ..Default::default()
+    }
+}
+
+//a dark box with a colored fill inside. the fill's width is the value.
+//the markers go on the box and the fill so systems can find them.
+pub(crate) fn spawn_bar(
+    parent: &mut ChildBuilder,
+    registry: &AssetRegistry,
+    style: Style,
+    size: Vec2,
+    fill: Handle<ColorMaterial>,
+    back_markers: impl Bundle,
+    fill_markers: impl Bundle,
+    ){
+    parent
+        .spawn_bundle(NodeBundle{
+            style: Style{
+                size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+                padding: Rect::all(Val::Px(2.0)),
+                ..style
+            },
+            material: registry.material("bar_back"),
+            ..Default::default()
+        })
+        .insert_bundle(back_markers)
+        .with_children(|bar|{
+            bar.spawn_bundle(NodeBundle{
+                    style: Style{
+                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                        ..Default::default()
+                    },
+                    material: fill,
+                    ..Default::default()
+                })
+                .insert_bundle(fill_markers);
+        });
+}
+
+//a strip across the window with its children centered in a column.
+//ColumnReverse because ui y counts up from the bottom.
+fn centered_strip(registry: &AssetRegistry, position: Rect<Val>) -> NodeBundle{
+    NodeBundle{
+        style: Style{
+            position_type: PositionType::Absolute,
+            position,
+            flex_direction: FlexDirection::ColumnReverse,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: registry.material("hud_clear"),
+        ..Default::default()
+    }
+}
+
+fn hud_spawn(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    ){
+    commands.spawn_bundle(UiCameraBundle::default());
+
+    commands
+        .spawn_bundle(NodeBundle{
+            style: Style{
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                ..Default::default()
+            },
+            material: registry.material("hud_clear"),
+            ..Default::default()
+        })
+        .insert(PlayArea)
+        .with_children(|hud|{
+            //top left, health over stamina
+            spawn_bar(hud, &registry,
+                pinned(Rect{
+                    left: Val::Px(MARGIN),
+                    top: Val::Px(MARGIN),
+                    ..Default::default()
+                }),
+                Vec2::new(BAR_WIDTH, BAR_HEIGHT), registry.material("hud_health"),
+                (), (HealthFill,));
+            spawn_bar(hud, &registry,
+                pinned(Rect{
+                    left: Val::Px(MARGIN),
+                    top: Val::Px(MARGIN * 2.0 + BAR_HEIGHT),
+                    ..Default::default()
+                }),
+                Vec2::new(BAR_WIDTH * 0.75, BAR_HEIGHT * 0.75), registry.material("hud_stamina"),
+                (), (StaminaFill,));
+
+            //top right, score over combo
+            hud.spawn_bundle(text_bundle(&registry, pinned(Rect{
+                    right: Val::Px(MARGIN),
+                    top: Val::Px(MARGIN),
+                    ..Default::default()
+                })))
+                .insert(ScoreText);
+            hud.spawn_bundle(text_bundle(&registry, pinned(Rect{
+                    right: Val::Px(MARGIN),
+                    top: Val::Px(MARGIN * 2.0 + FONT_SIZE),
+                    ..Default::default()
+                })))
+                .insert(ComboText);
+
+            //top middle
+            hud.spawn_bundle(centered_strip(&registry, Rect{
+                    left: Val::Px(0.0),
+                    right: Val::Px(0.0),
+                    top: Val::Px(MARGIN),
+                    ..Default::default()
+                }))
+                .with_children(|middle|{
+                    middle.spawn_bundle(text_bundle(&registry, Style::default()))
+                        .insert(DawnText);
+                });
+
+            //bottom middle, the boss's name over the bar
+            hud.spawn_bundle(centered_strip(&registry, Rect{
+                    left: Val::Px(0.0),
+                    right: Val::Px(0.0),
+                    bottom: Val::Px(MARGIN * 2.0),
+                    ..Default::default()
+                }))
+                .with_children(|bottom|{
+                    let mut name = text_bundle(&registry, Style::default());
+                    name.text.sections[0].value = "Minotaur".to_string();
+                    name.visible.is_visible = false;
+                    bottom.spawn_bundle(name).insert(BossBar);
+
+                    spawn_bar(bottom, &registry, Style::default(),
+                        Vec2::new(BOSS_BAR_WIDTH, BOSS_BAR_HEIGHT), registry.material("bar_boss"),
+                        (BossBar,), (BossBar, BossFill));
+                });
+        });
+}
+
+pub(crate) fn set_fill(style: &mut Style, amount: f32){
+    style.size.width = Val::Percent(amount.max(0.0).min(1.0) * 100.0);
+}
+
+fn hud_health(
+    player_query: Query<&Energy, With<Player>>,
+    mut fill_query: Query<&mut Style, With<HealthFill>>,
+    ){
+    if let (Ok(energy), Ok(mut style)) = (player_query.single(), fill_query.single_mut()){
+        set_fill(&mut style, energy.power as f32 / energy.max as f32);
+    }
+}
+
+fn hud_stamina(
+    player_query: Query<&Stamina, With<Player>>,
+    mut fill_query: Query<&mut Style, With<StaminaFill>>,
+    ){
+    if let (Ok(stamina), Ok(mut style)) = (player_query.single(), fill_query.single_mut()){
+        set_fill(&mut style, stamina.current / stamina.max);
+    }
+}
+
+fn hud_score(
+    score: Res<Score>,
+    mut query: Query<&mut Text, With<ScoreText>>,
+    ){
+    if let Ok(mut text) = query.single_mut(){
+        text.sections[0].value = format!("Score {}", score.points);
+    }
+}
+
+fn hud_combo(
+    score: Res<Score>,
+    mut query: Query<&mut Text, With<ComboText>>,
+    ){
+    if let Ok(mut text) = query.single_mut(){
+        text.sections[0].value = if score.combo > 1{
+            format!("{} hit combo", score.combo)
+        }else{
+            String::new()
+        };
+    }
+}
+
+fn hud_dawn(
+    night: Res<Night>,
+    mut query: Query<&mut Text, With<DawnText>>,
+    ){
+    if let Ok(mut text) = query.single_mut(){
+        text.sections[0].value = if night.until_dawn.finished(){
+            "Dawn".to_string()
+        }else{
+            let left = night.until_dawn.duration().as_secs_f32() - night.until_dawn.elapsed_secs();
+            let seconds = left.ceil() as u32;
+            format!("Dawn in {}:{:02}", seconds / 60, seconds % 60)
+        };
+    }
+}
+
+//only there while the boss is fighting.
+fn hud_boss(
+    boss_query: Query<(&Boss, &Energy)>,
+    mut part_query: Query<&mut Visible, With<BossBar>>,
+    mut fill_query: Query<&mut Style, With<BossFill>>,
+    ){
+    let boss = boss_query.iter().find(|(boss, _)| boss.fighting());
+    for mut visible in part_query.iter_mut(){
+        visible.is_visible = boss.is_some();
+    }
+    if let (Some((_, energy)), Ok(mut style)) = (boss, fill_query.single_mut()){
+        set_fill(&mut style, energy.power as f32 / energy.max as f32);
+    }
+}
@@ -0,0 +1,489 @@
+use bevy::prelude::*;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use bevy::sprite::collide_aabb::collide;
+use serde::Deserialize;
+
+use crate::{WinSize, Background, Velocity, Player, StrikeBox, Energy,
+    Element, LevelComplete, RestartRun, WorldScroll, AppState, SIMULATION, TIMESTEP};
+use crate::registry::AssetRegistry;
+use crate::tuning::Tuning;
+use crate::platforms::{PlatformSpec, spawn_platform};
+use crate::enemies::{spawn_skeleton, spawn_mage};
+use crate::boss::spawn_boss;
+use crate::tiled::TiledLoader;
+use crate::chunks::ChunkGenerator;
+use crate::ground::{PitSpec, Pits, warp_to};
+use crate::hazards::{HazardSpec, spawn_hazard};
+use crate::loading::LoadingAssets;
+use crate::night::Night;
+
+const FIRST_LEVEL: &str = "levels/level_01.level.ron";
+pub(crate) const MAX_PLAYER_POWER: i32 = 100;
+pub(crate) const GROUND_TOP: f32 = 10.0; //ground strip top, above the bottom of the screen
+
+//everything a level needs besides the player. written by hand in
+//assets/levels so it can change without touching the code, or 
+//made in tiled and imported by tiled.rs.
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "3f6c9e52-1b7d-4c8a-9e35-7a2d4b6f1c08"]
+pub(crate) struct Level{
+    pub(crate) parallax: ParallaxPreset,
+    pub(crate) platforms: Vec<PlatformSpec>,
+    #[serde(default)]
+    pub(crate) ground: Vec<GroundTiles>,
+    #[serde(default)]
+    pub(crate) pits: Vec<PitSpec>,
+    #[serde(default)]
+    pub(crate) hazards: Vec<HazardSpec>,
+    pub(crate) enemies: Vec<EnemySpawn>,
+    pub(crate) pickups: Vec<PickupSpec>,
+    pub(crate) checkpoints: Vec<f32>, //x of each checkpoint
+    #[serde(default)]
+    pub(crate) exit: Option<f32>, //x of the exit
+    #[serde(default)]
+    pub(crate) endless_seed: Option<u64>, //makes chunks forever instead, see chunks.rs
+}
+
+//how the background layers behind the action drift.
+#[derive(Deserialize)]
+pub(crate) enum ParallaxPreset{
+    Forest, //the normal depth
+    Distant, //far off, drifts slowly
+    Still, //painted backdrop, doesn't move
+}
+
+impl ParallaxPreset{
+    fn layer_speed(&self) -> f32{
+        match self{
+            ParallaxPreset::Forest => 1.0,
+            ParallaxPreset::Distant => 0.5,
+            ParallaxPreset::Still => 0.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub(crate) enum EnemyKind{
+    Skeleton,
+    FireMage,
+    WaterMage,
+    Minotaur,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct EnemySpawn{
+    pub(crate) kind: EnemyKind,
+    pub(crate) x: f32,
+    #[serde(default)]
+    pub(crate) power: Option<i32>, //overrides the enemy's usual energy
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub(crate) enum PickupKind{
+    Health(i32),
+}
+
+#[derive(Deserialize)]
+pub(crate) struct PickupSpec{
+    pub(crate) kind: PickupKind,
+    pub(crate) x: f32,
+    pub(crate) y: f32, //above the bottom of the screen
+}
+
+//tiles from a tile sheet drawn as ground. they scroll like platforms
+//but don't block anything, solid ground is in platforms.
+#[derive(Deserialize)]
+pub(crate) struct GroundTiles{
+    pub(crate) image: String, //path under assets
+    pub(crate) tile_size: Vec2,
+    pub(crate) columns: usize,
+    pub(crate) rows: usize,
+    pub(crate) tiles: Vec<(u32, f32, f32)>, //sheet index, center x, y above the bottom
+}
+
+//level props scroll with the world like platforms do.
+struct Prop;
+
+struct Pickup{
+    kind: PickupKind,
+}
+
+struct Checkpoint{
+    x: f32, //in the level, where it was before any scrolling
+    reached: bool,
+}
+
+struct Exit{
+    reached: bool,
+}
+
+//the level being played.
+struct CurrentLevel{
+    handle: Handle<Level>,
+    images_queued: bool, //its tile sheets are waited on too
+}
+
+//x in the level of the last checkpoint the player passed, if any.
+//a retry or a fall into a pit starts him from there. kept until the
+//level is won or another one loads.
+pub(crate) struct LastCheckpoint{
+    pub(crate) x: Option<f32>,
+}
+
+#[derive(Default)]
+struct LevelLoader;
+
+impl AssetLoader for LevelLoader{
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+        ) -> BoxedFuture<'a, Result<(), anyhow::Error>>{
+        Box::pin(async move{
+            let level: Level = ron::de::from_bytes(bytes)?;
+            for spec in level.hazards.iter(){
+                spec.kind.check()?;
+            }
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str]{
+        &["level.ron"]
+    }
+}
+
+pub struct LevelPlugin;
+impl Plugin for LevelPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .init_asset_loader::<TiledLoader>()
+            .insert_resource(LastCheckpoint{
+                x: None,
+            })
+            .add_startup_system(load_level.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Loading)
+                    .with_system(queue_level_images.system().before("check_loading"))
+                       )
+            .add_system(spawn_level.system().after("restart_world"))
+            .add_system(reload_level.system())
+            .add_system(clear_checkpoint.system())
+            .add_system_to_stage(SIMULATION,
+                scroll_props.system().label("scroll_world").after("update_scroll"))
+            .add_system_to_stage(SIMULATION, collect_pickups.system())
+            .add_system_to_stage(SIMULATION, reach_checkpoints.system())
+            .add_system_to_stage(SIMULATION, reach_exit.system());
+    }
+}
+
+//a different level can be given on the command line, 
+//e.g. cargo run -- levels/level_01.tmj. tiled maps as .tmj or .tmx.
+fn load_level(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut loading: ResMut<LoadingAssets>,
+    ){
+    let path = std::env::args().nth(1).unwrap_or(FIRST_LEVEL.to_string());
+    commands.insert_resource(CurrentLevel{
+        handle: loading.load(&asset_server, path.as_str()),
+        images_queued: false,
+    });
+}
+
+//the tile sheets a level uses are only known once the level is in.
+//added before check_loading looks, so the title can't come first.
+fn queue_level_images(
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<Level>>,
+    mut current: ResMut<CurrentLevel>,
+    mut loading: ResMut<LoadingAssets>,
+    ){
+    if current.images_queued{
+        return;
+    }
+    if let Some(level) = levels.get(&current.handle){
+        for ground in level.ground.iter(){
+            let _: Handle<Texture> = loading.load(&asset_server, ground.image.as_str());
+        }
+        current.images_queued = true;
+    }
+}
+
+//an edited level file is played from the top straight away.
+fn reload_level(
+    asset_server: Res<AssetServer>,
+    current: Res<CurrentLevel>,
+    mut level_events: EventReader<AssetEvent<Level>>,
+    mut restart_events: EventWriter<RestartRun>,
+    ){
+    for event in level_events.iter(){
+        if let AssetEvent::Modified{handle} = event{
+            if *handle == current.handle{
+                if let Some(path) = asset_server.get_handle_path(handle.id){
+                    info!("reloaded {}, starting the run over", path.path().display());
+                }
+                restart_events.send(RestartRun);
+            }
+        }
+    }
+}
+
+//the file loads in the background. fill the world once it's ready,
+//and again from the top on a retry.
+fn spawn_level(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut level_events: EventReader<AssetEvent<Level>>,
+    mut restart_events: EventReader<RestartRun>,
+    levels: Res<Assets<Level>>,
+    current: Res<CurrentLevel>,
+    registry: Res<AssetRegistry>,
+    tuning: Res<Tuning>,
+    window: Res<WinSize>,
+    mut scroll: ResMut<WorldScroll>,
+    mut pits: ResMut<Pits>,
+    mut night: ResMut<Night>,
+    mut last_checkpoint: ResMut<LastCheckpoint>,
+    mut bg_query: Query<(&Transform, &mut Velocity), With<Background>>,
+    prop_query: Query<Entity, With<Prop>>,
+    ){
+    let loaded = level_events.iter().any(|event| match event{
+        AssetEvent::Created{handle} => *handle == current.handle,
+        _ => false,
+    });
+    let restarted = restart_events.iter().count() > 0;
+    if !loaded && !restarted{
+        return;
+    }
+    let level = match levels.get(&current.handle){
+        Some(level) => level,
+        None => return,
+    };
+
+    //the other plugins clear their own things on a restart
+    if restarted{
+        for entity in prop_query.iter(){
+            commands.entity(entity).despawn();
+        }
+        commands.remove_resource::<ChunkGenerator>();
+    }else{
+        last_checkpoint.x = None;
+    }
+    //enemies behind the checkpoint were already beaten
+    let start = last_checkpoint.x.unwrap_or(std::f32::MIN);
+
+    let screen_bottom = -(window.h / 2.0);
+
+    //only the layers behind the action. the front ones have to
+    //keep up with the platforms.
+    for(transform, mut velocity) in bg_query.iter_mut(){
+        if transform.translation.z < 1.8{
+            velocity.velocity.x = level.parallax.layer_speed();
+        }
+    }
+
+    for spec in level.platforms.iter(){
+        spawn_platform(&mut commands, &registry, screen_bottom, spec);
+    }
+
+    for spec in level.hazards.iter(){
+        spawn_hazard(&mut commands, &registry, screen_bottom, spec);
+    }
+
+    //everything above is placed from x 0.0 as it is right now
+    pits.spans = level.pits.clone();
+    pits.origin = 0.0;
+
+    for spawn in level.enemies.iter().filter(|spawn| spawn.x >= start){
+        let enemy = match spawn.kind{
+            EnemyKind::Skeleton => spawn_skeleton(&mut commands, &registry, &tuning, spawn.x),
+            EnemyKind::FireMage => spawn_mage(&mut commands, &tuning,
+                registry.atlas("mage_fire"), Element::Fire, spawn.x),
+            EnemyKind::WaterMage => spawn_mage(&mut commands, &tuning,
+                registry.atlas("mage_water"), Element::Water, spawn.x),
+            EnemyKind::Minotaur => spawn_boss(&mut commands, &registry, &tuning, spawn.x),
+        };
+        if let Some(power) = spawn.power{
+            commands.entity(enemy).insert(Energy{
+                power,
+                max: power,
+            });
+        }
+    }
+
+    for ground in level.ground.iter(){
+        let texture_atlas = texture_atlases.add(TextureAtlas::from_grid(
+            asset_server.load(ground.image.as_str()), ground.tile_size, 
+            ground.columns, ground.rows));
+        for(index, x, y) in ground.tiles.iter(){
+            commands
+                .spawn_bundle(SpriteSheetBundle{
+                    texture_atlas: texture_atlas.clone(),
+                    sprite: TextureAtlasSprite::new(*index),
+                    transform: Transform{
+                        translation: Vec3::new(*x, screen_bottom + *y, 1.9),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(Prop)
+                .insert(Velocity{
+                    velocity: Vec3::new(1.0, 0.0, 0.0),
+                });
+        }
+    }
+
+    for pickup in level.pickups.iter(){
+        spawn_prop(&mut commands, registry.material("pickup_health"),
+            Vec3::new(pickup.x, screen_bottom + pickup.y, 1.9), Vec2::new(20.0, 20.0))
+            .insert(Pickup{
+                kind: pickup.kind,
+            });
+    }
+
+    for x in level.checkpoints.iter(){
+        let reached = *x <= start;
+        let material = if reached{ "checkpoint_lit" }else{ "checkpoint" };
+        spawn_prop(&mut commands, registry.material(material),
+            Vec3::new(*x, screen_bottom + GROUND_TOP + 50.0, 1.7), Vec2::new(10.0, 100.0))
+            .insert(Checkpoint{
+                x: *x,
+                reached,
+            });
+    }
+
+    if let Some(exit) = level.exit{
+        spawn_prop(&mut commands, registry.material("exit"),
+            Vec3::new(exit, screen_bottom + GROUND_TOP + 60.0, 1.7), Vec2::new(40.0, 120.0))
+            .insert(Exit{
+                reached: false,
+            });
+    }
+
+    //with no exit the only way out is to last until morning. an
+    //endless level has no way out, dawn just comes.
+    night.begin(level.exit.is_none() && level.endless_seed.is_none());
+
+    if let Some(seed) = level.endless_seed{
+        commands.insert_resource(ChunkGenerator::new(seed));
+    }
+
+    //the player starts at x 0.0, bring the checkpoint to him
+    if let Some(x) = last_checkpoint.x{
+        warp_to(&mut scroll, &pits, 0.0, x);
+    }
+}
+
+fn spawn_prop<'a, 'b>(
+    commands: &'b mut Commands<'a>,
+    material: Handle<ColorMaterial>,
+    translation: Vec3,
+    size: Vec2,
+    ) -> bevy::ecs::system::EntityCommands<'a, 'b>{
+    let mut prop = commands.spawn_bundle(SpriteBundle{
+        material,
+        sprite: Sprite::new(size),
+        transform: Transform{
+            translation,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    prop
+        .insert(Prop)
+        .insert(Velocity{
+            velocity: Vec3::new(1.0, 0.0, 0.0),
+        });
+    prop
+}
+
+fn scroll_props(
+    scroll: Res<WorldScroll>,
+    mut query: Query<(&mut Transform, &Velocity), With<Prop>>,
+    ){
+    for(mut transform, velocity) in query.iter_mut(){
+        //multiplyer is the z value, same as the platforms.
+        let multiplier = transform.translation.z;
+        transform.translation +=
+            (scroll.speed * multiplier) * velocity.velocity * TIMESTEP;
+    }
+}
+
+fn touching(player_tf: &Transform, strike_box: &StrikeBox, prop_tf: &Transform, sprite: &Sprite) -> bool{
+    collide(
+        player_tf.translation,
+        Vec2::new(strike_box.h, strike_box.w),
+        prop_tf.translation,
+        sprite.size,
+        ).is_some()
+}
+
+fn collect_pickups(
+    mut commands: Commands,
+    mut player_query: Query<(&Transform, &StrikeBox, &mut Energy), With<Player>>,
+    pickup_query: Query<(Entity, &Pickup, &Transform, &Sprite)>,
+    ){
+    if let Ok((player_tf, strike_box, mut energy)) = player_query.single_mut(){
+        for(entity, pickup, pickup_tf, sprite) in pickup_query.iter(){
+            if !touching(player_tf, strike_box, pickup_tf, sprite){
+                continue;
+            }
+            match pickup.kind{
+                PickupKind::Health(amount) => {
+                    energy.power = (energy.power + amount).min(MAX_PLAYER_POWER);
+                }
+            }
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn reach_checkpoints(
+    mut last_checkpoint: ResMut<LastCheckpoint>,
+    registry: Res<AssetRegistry>,
+    player_query: Query<(&Transform, &StrikeBox), With<Player>>,
+    mut checkpoint_query: Query<(&mut Checkpoint, &Transform, &Sprite, &mut Handle<ColorMaterial>)>,
+    ){
+    if let Ok((player_tf, strike_box)) = player_query.single(){
+        for(mut checkpoint, checkpoint_tf, sprite, mut material) in checkpoint_query.iter_mut(){
+            if checkpoint.reached || !touching(player_tf, strike_box, checkpoint_tf, sprite){
+                continue;
+            }
+            checkpoint.reached = true;
+            *material = registry.material("checkpoint_lit");
+            last_checkpoint.x = Some(checkpoint.x);
+        }
+    }
+}
+
+fn reach_exit(
+    mut level_events: EventWriter<LevelComplete>,
+    player_query: Query<(&Transform, &StrikeBox), With<Player>>,
+    mut exit_query: Query<(&mut Exit, &Transform, &Sprite)>,
+    ){
+    if let Ok((player_tf, strike_box)) = player_query.single(){
+        for(mut exit, exit_tf, sprite) in exit_query.iter_mut(){
+            if !exit.reached && touching(player_tf, strike_box, exit_tf, sprite){
+                exit.reached = true;
+                level_events.send(LevelComplete);
+            }
+        }
+    }
+}
+
+//a won level starts over from the top.
+fn clear_checkpoint(
+    mut level_events: EventReader<LevelComplete>,
+    mut last_checkpoint: ResMut<LastCheckpoint>,
+    ){
+    for _ in level_events.iter(){
+        last_checkpoint.x = None;
+    }
+}
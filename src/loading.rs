@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use bevy::app::AppExit;
+use bevy::asset::{Asset, LoadState};
+
+use crate::{AppState};
+use crate::registry::AssetRegistry;
+use crate::hud::{spawn_bar, set_fill};
+use crate::screens::{ScreenUi, spawn_screen, clear_screen};
+
+const BAR_WIDTH: f32 = 400.0;
+const BAR_HEIGHT: f32 = 18.0;
+//a file that fails to decode never leaves Loading in bevy 0.5, so
+//if nothing finishes for this long whatever is left is broken.
+const STALL_TIME: f32 = 10.0;
+
+//every asset the game can't start without. load through here and it
+//gets waited on before the title screen.
+pub(crate) struct LoadingAssets{
+    handles: Vec<HandleUntyped>,
+    loaded: usize,
+    stalled: Timer,
+    failed: bool,
+}
+
+impl LoadingAssets{
+    pub(crate) fn load<T: Asset>(&mut self, asset_server: &AssetServer, path: &str) -> Handle<T>{
+        let handle = asset_server.load(path);
+        self.handles.push(handle.clone_untyped());
+        handle
+    }
+}
+
+struct LoadingFill;
+struct LoadingText;
+
+pub struct LoadingPlugin;
+impl Plugin for LoadingPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .insert_resource(LoadingAssets{
+                handles: Vec::new(),
+                loaded: 0,
+                stalled: Timer::from_seconds(STALL_TIME, false),
+                failed: false,
+            })
+            .add_system_set(
+                SystemSet::on_enter(AppState::Loading)
+                    .with_system(loading_spawn.system())
+                       )
+            .add_system_set(
+                SystemSet::on_update(AppState::Loading)
+                    .with_system(check_loading.system().label("check_loading"))
+                    .with_system(loading_input.system())
+                       )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Loading)
+                    .with_system(clear_screen.system())
+                       );
+    }
+}
+
+fn loading_spawn(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    ){
+    let screen = spawn_screen(&mut commands, &registry, registry.material("screen_solid"),
+        &["Loading".to_string()]);
+    commands.entity(screen).with_children(|screen|{
+        spawn_bar(screen, &registry, Style::default(),
+            Vec2::new(BAR_WIDTH, BAR_HEIGHT), registry.material("hud_health"),
+            (), (LoadingFill,));
+        screen.spawn_bundle(TextBundle{
+                style: Style{
+                    margin: Rect::all(Val::Px(8.0)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "",
+                    TextStyle{
+                        font: registry.font("hud"),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                    ),
+                ..Default::default()
+            })
+            .insert(LoadingText);
+    });
+}
+
+fn asset_name(asset_server: &AssetServer, handle: &HandleUntyped) -> String{
+    match asset_server.get_handle_path(handle.id){
+        Some(path) => path.path().display().to_string(),
+        None => "an unnamed asset".to_string(),
+    }
+}
+
+fn check_loading(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    registry: Res<AssetRegistry>,
+    mut loading: ResMut<LoadingAssets>,
+    mut state: ResMut<State<AppState>>,
+    mut fill_query: Query<&mut Style, With<LoadingFill>>,
+    mut text_query: Query<&mut Text, With<LoadingText>>,
+    screen_query: Query<Entity, With<ScreenUi>>,
+    ){
+    if loading.failed{
+        return;
+    }
+
+    let group = asset_server.get_group_load_state(loading.handles.iter().map(|handle| handle.id));
+    if group == LoadState::Loaded{
+        state.set(AppState::Title).unwrap();
+        return;
+    }
+
+    let loaded = loading.handles.iter()
+        .filter(|handle| asset_server.get_load_state(handle.id) == LoadState::Loaded)
+        .count();
+    if loaded > loading.loaded{
+        loading.loaded = loaded;
+        loading.stalled.reset();
+    }
+    loading.stalled.tick(time.delta());
+
+    //Failed is a missing file. NotLoaded is a load still waiting its
+    //turn on the io threads, or a file nothing can load, so like a
+    //load that never finishes it only counts once things stall.
+    let broken: Vec<String> = loading.handles.iter()
+        .filter(|handle| match asset_server.get_load_state(handle.id){
+            LoadState::Failed => true,
+            LoadState::NotLoaded | LoadState::Loading => loading.stalled.finished(),
+            LoadState::Loaded => false,
+        })
+        .map(|handle| asset_name(&asset_server, handle))
+        .collect();
+
+    if broken.is_empty(){
+        let total = loading.handles.len();
+        if let Ok(mut style) = fill_query.single_mut(){
+            set_fill(&mut style, loaded as f32 / total.max(1) as f32);
+        }
+        if let Ok(mut text) = text_query.single_mut(){
+            text.sections[0].value = format!("{} of {}", loaded, total);
+        }
+        return;
+    }
+
+    //the font might be what broke, so say it in the log too
+    let mut lines = vec!["Could not load the game".to_string()];
+    for name in broken.iter(){
+        error!("could not load {}", name);
+        lines.push(format!("{} is missing or broken", name));
+    }
+    lines.push("Esc to quit".to_string());
+    for entity in screen_query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_screen(&mut commands, &registry, registry.material("screen_solid"), &lines);
+    loading.failed = true;
+}
+
+fn loading_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    loading: Res<LoadingAssets>,
+    mut exit_events: EventWriter<AppExit>,
+    ){
+    if loading.failed && keyboard_input.just_pressed(KeyCode::Escape){
+        exit_events.send(AppExit);
+    }
+}
@@ -1,32 +1,68 @@
 use bevy::prelude::*;
+use bevy::core::FixedTimestep;
+use bevy::ecs::schedule::ShouldRun;
 use bevy::sprite::collide_aabb::collide;
+use serde::Deserialize;
 
 
 mod backgrounds;
 mod players;
 mod platforms;
 mod enemies;
+mod projectiles;
+mod status;
+mod boss;
+mod telegraph;
+mod physics;
+mod level;
+mod tiled;
+mod chunks;
+mod ground;
+mod hazards;
+mod sound;
+mod music;
+mod score;
+mod hud;
+mod floating;
+mod screens;
+mod loading;
+mod registry;
+mod tuning;
+mod display;
+mod night;
+mod stamina;
+mod damage;
 use backgrounds::BackgroundsPlugin;
 use players::PlayersPlugin;
 use platforms::PlatformsPlugin;
 use enemies::EnemiesPlugin; 
-
-
-const BG_NIGHT: &str = "Background/Layer_0010_1.png";
-const BG_02: &str = "Background/Layer_0009_2.png";
-const BG_03: &str = "Background/Layer_0008_3.png";
-const BG_04: &str = "Background/Layer_0006_4.png";
-const BG_05: &str = "Background/Layer_0005_5.png";
-const BG_06: &str = "Background/Layer_0003_6.png";
-const BG_07: &str = "Background/Layer_0002_7.png";
-const BG_08: &str = "Background/Layer_0001_8.png";
-const BG_09: &str = "Background/Layer_0000_9.png";
-const BG_10: &str = "Background/Layer_0007_Lights.png";
-
-//game assets
+use projectiles::ProjectilesPlugin;
+use status::StatusPlugin;
+use boss::{BossPlugin, Boss};
+use telegraph::TelegraphPlugin;
+use physics::PhysicsPlugin;
+use level::LevelPlugin;
+use chunks::ChunksPlugin;
+use ground::GroundPlugin;
+use hazards::HazardsPlugin;
+use sound::SoundPlugin;
+use music::MusicPlugin;
+use score::ScorePlugin;
+use hud::HudPlugin;
+use floating::FloatingPlugin;
+use screens::ScreensPlugin;
+use loading::LoadingPlugin;
+use registry::RegistryPlugin;
+use tuning::TuningPlugin;
+use display::{DisplayPlugin, LOGICAL_W, LOGICAL_H};
+use night::NightPlugin;
+use stamina::StaminaPlugin;
+use damage::{DamagePlugin, CritRng, CHOP_DAMAGE, CHARGE_DAMAGE};
+use score::PlayerHit;
+
+
+//game assets, the files themselves are listed in assets/manifest.ron
 const SCALE_UP: f32 = 3.5;
-const PLAYER_SPRITE: &str = "anim/player1.png";
-const SKELY_ONE_SPRITE: &str = "anim/Skeleton1_64x48.png";
 
 //game values
 const SPEEDFAST: f32 = 300.0;
@@ -39,24 +75,58 @@ const ENEMYSPEEDMED: f32 = 75.0;
 const ENEMYSPEEDSLOW: f32 = 50.0;
 const ENEMYSPEEDSTOP: f32 = 0.0;  //here for consistancy.  
 
+//movement, platform contact and combat run in this stage at a fixed
+//rate so every machine gets the same jumps and hits.
+const SIMULATION: &str = "simulation";
+const SIMULATION_TIMESTEP: &str = "simulation_timestep";
+const TIMESTEP: f32 = 1.0 / 60.0;
+
 //resources
-pub struct Materials{
-    background: Handle<ColorMaterial>,
-    bg_02: Handle<ColorMaterial>,
-    bg_03: Handle<ColorMaterial>,
-    bg_04: Handle<ColorMaterial>,
-    bg_05: Handle<ColorMaterial>,
-    bg_06: Handle<ColorMaterial>,
-    bg_07: Handle<ColorMaterial>,
-    bg_08: Handle<ColorMaterial>,
-    bg_09: Handle<ColorMaterial>,
-    bg_10: Handle<ColorMaterial>,
-    //game assets
-    player_sprite: Handle<TextureAtlas>,
-    skely_one_sprite: Handle<TextureAtlas>,
-    //platform
-    pl_01: Handle<ColorMaterial>,
+//while locked the world stops scrolling and the player
+//moves around the arena instead.
+pub struct ArenaLock{
+    locked: bool,
 }
+
+//which screen the game is on. gameplay only runs in Playing,
+//Paused is pushed on top of it. see screens.rs
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum AppState{
+    Loading,
+    Title,
+    Playing,
+    Paused,
+    GameOver,
+    Victory, //the level was won
+}
+
+//scales how forgiving the game is. more settings will hang off this.
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Difficulty{
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty{
+    //multiplier on enemy wind-up times
+    fn telegraph_scale(&self) -> f32{
+        match self{
+            Difficulty::Easy => 1.5,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 0.6,
+        }
+    }
+}
+
+//how fast the world is sliding past the player this frame.
+//scroll systems multiply it by their z depth. set in physics.rs
+pub struct WorldScroll{
+    speed: f32,
+    warp: f32, //one step jump of the whole world, for respawning
+}
+
+//the play area, not the window. see display.rs
 pub struct WinSize{
     h: f32,
     w: f32,
@@ -86,6 +156,25 @@ struct StrikeBox{
 struct Background;
 struct Platform;
 
+//how a platform behaves. picked per platform in the level data.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+enum PlatformKind{
+    Solid,
+    OneWay, //jump up through it, Down+Jump drops through
+    Moving{path: Vec<Vec2>, speed: f32}, //path points are offsets from the spawn spot
+    Crumbling{delay: f32}, //seconds after it is first stood on
+    Bounce{speed: f32}, //launch speed
+}
+
+//a moving platform's place on its path.
+struct PlatformPath{
+    points: Vec<Vec2>,
+    speed: f32,
+    next: usize, //point it is heading for
+    offset: Vec2, //how far it is from the spawn spot
+    velocity: Vec2, //last step, riders get carried by this
+}
+
 
 struct Velocity{
     velocity: Vec3,
@@ -95,6 +184,28 @@ struct Gravity{
     falling: bool,
 }
 
+//how a body gets up to speed. movement code sets target_vx and
+//physics.rs eases velocity.x toward it every frame.
+struct Body{
+    target_vx: f32,
+    accel: f32, //px/s/s when speeding up
+    decel: f32, //px/s/s when slowing down or turning
+    air_control: f32, //share of accel and decel while falling, 0.0 - 1.0
+}
+
+//what physics.rs found when it swept a body against the platforms
+//this step. side is the side of the platform that was hit.
+struct Contact{
+    ground: bool,
+    side: Option<Side>,
+    platform: Option<Entity>, //what it is standing on
+}
+
+//while this is on a body it falls through one-way platforms.
+struct DropThrough{
+    timer: Timer,
+}
+
 struct Proximity{
     near_player: bool,
 }
@@ -105,9 +216,80 @@ struct Attacking{
 
 struct Energy{
     power: i32,
+    max: i32, //full health
 }
 
-#[derive(PartialEq, Eq)]
+//marks the melee skeletons so their ai and animation don't
+//run on the other enemy types.
+struct Skeleton;
+
+struct Mage{
+    element: Element,
+    cast_timer: Timer, //time between spells
+}
+
+//any hit that takes energy away goes through this event.
+struct Damage{
+    target: Entity,
+    amount: i32,
+    kind: DamageKind,
+}
+
+//hook for anything that wants to burn, freeze, stun or knock down
+//a body. see status.rs
+struct ApplyStatus{
+    target: Entity,
+    kind: StatusKind,
+    duration: f32,
+}
+
+//push a body away from from_x. force is the starting speed,
+//stun is how long it loses control (0.0 for none).
+struct Knockback{
+    target: Entity,
+    from_x: f32,
+    force: f32,
+    stun: f32,
+}
+
+//sent when a falling body comes down on top of a platform.
+//impact_speed is how fast it was falling, always positive.
+struct Landed{
+    entity: Entity,
+    impact_speed: f32,
+}
+
+//sent when the level's goal is reached (boss down)
+struct LevelComplete;
+
+//sent when the player retries after game over. each plugin clears
+//out what it spawned and puts its state back to the start.
+struct RestartRun;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum StatusKind{
+    Burn,
+    Freeze,
+    Stun,
+    Knockdown,
+}
+
+//how a hit shows up over the target. see floating.rs
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum DamageKind{
+    Normal,
+    Blocked, //went into a guard and only some got through
+    Critical,
+    Status, //burn ticks and the like
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Element{
+    Fire,
+    Water,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
 enum PlayerAction{
     Block,
     Charge,
@@ -118,9 +300,18 @@ enum PlayerAction{
     Pushed,
     Bumped,
     Fly,
+    WindUp, //enemy telegraphing an attack
 }
 
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side{
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
 #[derive(PartialEq, Eq)]
 enum Direction{
     Left,
@@ -136,96 +327,98 @@ fn main() {
         .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
         .insert_resource(WindowDescriptor{
             title: "Night Ward".to_string(),
-            width: 900.0,
-            height: 700.0,
+            width: LOGICAL_W,
+            height: LOGICAL_H,
             ..Default::default()
         })
         .add_plugins(DefaultPlugins)
+        .add_stage_after(
+            CoreStage::Update,
+            SIMULATION,
+            SystemStage::parallel()
+                .with_run_criteria(
+                    FixedTimestep::step(TIMESTEP as f64).with_label(SIMULATION_TIMESTEP)
+                        .chain(while_playing.system())),
+          )
+        .add_state(AppState::Loading)
         .add_plugin(BackgroundsPlugin)
         .add_plugin(PlayersPlugin)
         .add_plugin(PlatformsPlugin)
         .add_plugin(EnemiesPlugin)
-        .add_startup_system(setup.system())
-        .add_system(gravity_all.system())
-        .add_system(player_enemy_collision.system())
+        .add_plugin(ProjectilesPlugin)
+        .add_plugin(StatusPlugin)
+        .add_plugin(BossPlugin)
+        .add_plugin(TelegraphPlugin)
+        .add_plugin(PhysicsPlugin)
+        .add_plugin(LevelPlugin)
+        .add_plugin(ChunksPlugin)
+        .add_plugin(GroundPlugin)
+        .add_plugin(HazardsPlugin)
+        .add_plugin(SoundPlugin)
+        .add_plugin(MusicPlugin)
+        .add_plugin(ScorePlugin)
+        .add_plugin(HudPlugin)
+        .add_plugin(FloatingPlugin)
+        .add_plugin(ScreensPlugin)
+        .add_plugin(LoadingPlugin)
+        .add_plugin(RegistryPlugin)
+        .add_plugin(TuningPlugin)
+        .add_plugin(DisplayPlugin)
+        .add_plugin(NightPlugin)
+        .add_plugin(StaminaPlugin)
+        .add_plugin(DamagePlugin)
+        .insert_resource(WorldScroll{
+            speed: 0.0,
+            warp: 0.0,
+        })
+        .insert_resource(Difficulty::Normal)
+        .insert_resource(ArenaLock{
+            locked: false,
+        })
+        .add_event::<Damage>()
+        .add_event::<ApplyStatus>()
+        .add_event::<LevelComplete>()
+        .add_event::<RestartRun>()
+        .add_event::<Knockback>()
+        .add_event::<Landed>()
+        .add_system_to_stage(SIMULATION, player_enemy_collision.system().label("deal_damage"))
+        .add_system(end_level.system())
+        .add_system(restart_world.system().label("restart_world"))
         .run();
 }
 
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    mut windows: ResMut<Windows>,
-    ){
-
-    let mut window = windows.get_primary_mut().unwrap();
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-
-    //create texture atless for player
-    let texture_handle_player = asset_server.load(PLAYER_SPRITE);
-    let texture_atlas_player =
-        TextureAtlas::from_grid(
-            texture_handle_player, Vec2::new(64.0, 48.0), 9,4 
-                               );
-    //skeleton atlas
-    let texture_handle_skelyone = asset_server.load(SKELY_ONE_SPRITE);
-    let texture_atlas_skelyone = 
-        TextureAtlas::from_grid(
-            texture_handle_skelyone, Vec2::new(64.0, 48.0), 9, 4
-                               );
-    //build resources
-    commands.insert_resource(Materials{
-        background: materials.add(asset_server.load(BG_NIGHT).into()),
-        bg_02: materials.add(asset_server.load(BG_02).into()),
-        bg_03: materials.add(asset_server.load(BG_03).into()),
-        bg_04: materials.add(asset_server.load(BG_04).into()),
-        bg_05: materials.add(asset_server.load(BG_05).into()),
-        bg_06: materials.add(asset_server.load(BG_06).into()),
-        bg_07: materials.add(asset_server.load(BG_07).into()),
-        bg_08: materials.add(asset_server.load(BG_08).into()),
-        bg_09: materials.add(asset_server.load(BG_09).into()),
-        bg_10: materials.add(asset_server.load(BG_10).into()),
-        player_sprite: texture_atlases.add(texture_atlas_player),
-        skely_one_sprite: texture_atlases.add(texture_atlas_skelyone),
-        pl_01: materials.add(Color::rgb(0.7, 0.7, 0.7).into()),
-    });
-
-
-    commands.insert_resource(WinSize{
-        h: window.height(),
-        w: window.width(),
-    });
-}
-
-
-fn gravity_all(
-    time: Res<Time>,
-    mut query: Query<(&mut Transform, &mut Velocity, &Gravity)>,
-    ){
-    for(mut transform, mut entity_velocity, gravity) in query.iter_mut(){
-        if gravity.falling{
-            let delta_seconds = f32::min(0.3, time.delta_seconds());
-            let g = 800.0 * Vec3::new(0.0, -2.0, 0.0).normalize();
-            transform.translation += entity_velocity.velocity * delta_seconds;
-            entity_velocity.velocity = entity_velocity.velocity + (g * delta_seconds);
-        }
+//piped after a timestep, so timed gameplay stops whenever the game
+//isn't being played. NoAndCheckAgain lets the timestep use up the
+//time that passed, so nothing has to catch up on unpause.
+fn while_playing(
+    In(should_run): In<ShouldRun>,
+    state: Res<State<AppState>>,
+    ) -> ShouldRun{
+    if *state.current() == AppState::Playing{
+        return should_run;
+    }
+    match should_run{
+        ShouldRun::YesAndCheckAgain | ShouldRun::NoAndCheckAgain => ShouldRun::NoAndCheckAgain,
+        _ => ShouldRun::No,
     }
 }
 
-
 fn player_enemy_collision(
     mut commands: Commands,
+    mut crits: ResMut<CritRng>,
+    mut damage_events: EventWriter<Damage>,
+    mut status_events: EventWriter<ApplyStatus>,
+    mut hit_events: EventWriter<PlayerHit>,
     mut player_query: Query<(
         &mut Player, &Transform, &StrikeBox, &Attacking)>,
     mut enemy_query: Query<(
-        &mut Enemy, &Transform,  &StrikeBox)>,
+        Entity, &mut Enemy, &Transform,  &StrikeBox), Without<Boss>>,
     ){
 
     let mut on_something = false;
 
     for(mut player, player_tf, strike_box, player_attacking) in player_query.iter_mut(){
-        for(mut enemy, enemy_tf, strike_box) in enemy_query.iter_mut(){
+        for(enemy_entity, mut enemy, enemy_tf, strike_box) in enemy_query.iter_mut(){
 
             let player_size= Vec2::new(strike_box.h, strike_box.w);
             let enemy_size = Vec2::new(strike_box.h, strike_box.w);
@@ -240,11 +433,71 @@ fn player_enemy_collision(
 
             if let Some(_) = collision{
                 if player_attacking.attack{
+                    if enemy.action != PlayerAction::Bumped{
+                        hit_events.send(PlayerHit{
+                            points: if player.action == PlayerAction::Charge{ 20 }else{ 10 },
+                        });
+                        let base = if player.action == PlayerAction::Charge{
+                            CHARGE_DAMAGE
+                        }else{
+                            CHOP_DAMAGE
+                        };
+                        //a guarding enemy only takes a quarter
+                        let (amount, kind) = if enemy.action == PlayerAction::Block{
+                            (base / 4, DamageKind::Blocked)
+                        }else{
+                            crits.roll(base)
+                        };
+                        damage_events.send(Damage{
+                            target: enemy_entity,
+                            amount,
+                            kind,
+                        });
+                    }
                     enemy.action = PlayerAction::Bumped;
+                    //a charge knocks enemies down, a chop rattles them.
+                    if player.action == PlayerAction::Charge{
+                        status_events.send(ApplyStatus{
+                            target: enemy_entity,
+                            kind: StatusKind::Knockdown,
+                            duration: 1.0,
+                        });
+                    }else if player.action == PlayerAction::Chop{
+                        status_events.send(ApplyStatus{
+                            target: enemy_entity,
+                            kind: StatusKind::Stun,
+                            duration: 0.5,
+                        });
+                    }
                 }
             }
         }
     }
 }
 
+fn end_level(
+    mut commands: Commands,
+    mut level_events: EventReader<LevelComplete>,
+    enemy_query: Query<(Entity, &Energy), With<Enemy>>,
+    ){
+    //clear the field once the level is won. the fallen boss stays.
+    for _ in level_events.iter(){
+        for (entity, energy) in enemy_query.iter(){
+            if energy.power > 0{
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
 
+fn restart_world(
+    mut restart_events: EventReader<RestartRun>,
+    mut scroll: ResMut<WorldScroll>,
+    mut arena: ResMut<ArenaLock>,
+    ){
+    for _ in restart_events.iter(){
+        scroll.speed = 0.0;
+        scroll.warp = 0.0;
+        arena.locked = false;
+    }
+}
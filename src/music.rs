@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use rodio::{Sink, Source};
+
+use crate::{Enemy, Proximity};
+use crate::boss::Boss;
+use crate::sound::{SoundOutput, VolumeBuses};
+use crate::registry::AssetRegistry;
+
+const FADE_TIME: f32 = 2.0; //seconds for a layer to fade all the way in or out
+const AMBIENCE_UNDER_MUSIC: f32 = 0.4; //the night bed ducks under the fighting
+
+//the music is layers that all loop the whole time. fading a layer
+//is only ever a change of volume, so they stay in step.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Layer{
+    Ambience,
+    Combat,
+    Boss,
+}
+
+const LAYERS: [Layer; 3] = [Layer::Ambience, Layer::Combat, Layer::Boss];
+
+//id in the manifest, volume at full. a track that isn't in
+//assets/Music yet just never starts, the rest still play.
+fn layer_track(layer: Layer) -> (&'static str, f32){
+    match layer{
+        Layer::Ambience => ("ambience", 0.5),
+        Layer::Combat => ("combat", 0.8),
+        Layer::Boss => ("boss", 0.9),
+    }
+}
+
+struct MusicLayer{
+    layer: Layer,
+    handle: Handle<AudioSource>,
+    sink: Option<Sink>, //made once the track has loaded
+    gain: f32, //where the fade is now, 0.0 to 1.0
+    target: f32,
+}
+
+//sinks can't leave the main thread, same as the output they play on.
+struct Music{
+    layers: Vec<MusicLayer>,
+}
+
+pub struct MusicPlugin;
+impl Plugin for MusicPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .insert_non_send_resource(Music{
+                layers: Vec::new(),
+            })
+            .add_startup_system(load_music.system())
+            .add_system(pick_layers.system().label("pick_layers"))
+            .add_system(fade_layers.system().after("pick_layers"));
+    }
+}
+
+fn load_music(
+    mut music: NonSendMut<Music>,
+    registry: Res<AssetRegistry>,
+    ){
+    music.layers = LAYERS.iter()
+        .map(|layer| MusicLayer{
+            layer: *layer,
+            handle: registry.music(layer_track(*layer).0),
+            sink: None,
+            gain: 0.0,
+            target: 0.0,
+        })
+        .collect();
+}
+
+//the boss track beats the combat music, which beats quiet.
+fn pick_layers(
+    mut music: NonSendMut<Music>,
+    enemy_query: Query<&Proximity, With<Enemy>>,
+    boss_query: Query<&Boss>,
+    ){
+    let boss = boss_query.iter().any(|boss| boss.fighting());
+    let combat = !boss && enemy_query.iter().any(|proximity| proximity.near_player);
+
+    for layer in music.layers.iter_mut(){
+        layer.target = match layer.layer{
+            Layer::Ambience if boss || combat => AMBIENCE_UNDER_MUSIC,
+            Layer::Ambience => 1.0,
+            Layer::Combat if combat => 1.0,
+            Layer::Boss if boss => 1.0,
+            _ => 0.0,
+        };
+    }
+}
+
+//one layer fading out while another fades in is the crossfade.
+fn fade_layers(
+    mut music: NonSendMut<Music>,
+    output: NonSend<SoundOutput>,
+    buses: Res<VolumeBuses>,
+    time: Res<Time>,
+    sources: Res<Assets<AudioSource>>,
+    ){
+    let step = time.delta_seconds() / FADE_TIME;
+
+    for layer in music.layers.iter_mut(){
+        if layer.sink.is_none(){
+            if let (Some((_, stream_handle)), Some(source)) = (&output.stream, sources.get(&layer.handle)){
+                if let Ok(sink) = Sink::try_new(stream_handle){
+                    sink.set_volume(0.0);
+                    sink.append(source.decoder().repeat_infinite());
+                    layer.sink = Some(sink);
+                }
+            }
+        }
+
+        let diff = layer.target - layer.gain;
+        layer.gain = if diff.abs() <= step{
+            layer.target
+        }else{
+            layer.gain + step * diff.signum()
+        };
+
+        if let Some(sink) = &layer.sink{
+            let full = layer_track(layer.layer).1;
+            sink.set_volume(layer.gain * full * buses.music * buses.master);
+        }
+    }
+}
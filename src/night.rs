@@ -0,0 +1,41 @@
+use bevy::prelude::*;
+
+use crate::{LevelComplete, SIMULATION, TIMESTEP};
+
+const NIGHT_LENGTH: f32 = 600.0; //seconds from spawning in to dawn
+
+//counts down to dawn. the hud shows it.
+pub(crate) struct Night{
+    pub(crate) until_dawn: Timer,
+    dawn_wins: bool, //dawn finishes the level
+}
+
+impl Night{
+    //every level starts at nightfall. see level.rs
+    pub(crate) fn begin(&mut self, dawn_wins: bool){
+        self.until_dawn.reset();
+        self.dawn_wins = dawn_wins;
+    }
+}
+
+pub struct NightPlugin;
+impl Plugin for NightPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .insert_resource(Night{
+                until_dawn: Timer::from_seconds(NIGHT_LENGTH, false),
+                dawn_wins: false,
+            })
+            .add_system_to_stage(SIMULATION, pass_night.system());
+    }
+}
+
+fn pass_night(
+    mut level_events: EventWriter<LevelComplete>,
+    mut night: ResMut<Night>,
+    ){
+    night.until_dawn.tick(std::time::Duration::from_secs_f32(TIMESTEP));
+    if night.until_dawn.just_finished() && night.dawn_wins{
+        level_events.send(LevelComplete);
+    }
+}
@@ -0,0 +1,498 @@
+use bevy::prelude::*;
+use bevy::core::FixedTimesteps;
+use bevy::transform::TransformSystem;
+
+use crate::{Player, Velocity, Gravity, Body, Contact, Side, Landed, StrikeBox, Platform,
+    PlatformKind, PlatformPath, DropThrough,
+    Knockback, ApplyStatus, StatusKind, ArenaLock, WorldScroll, AppState,
+    SIMULATION, SIMULATION_TIMESTEP, TIMESTEP};
+use crate::status::StatusEffects;
+use crate::tuning::Tuning;
+
+const SNAP_DISTANCE: f32 = 100.0; //farther than this in one step is a teleport
+const GROUND_PROBE: f32 = 1.0; //how close feet have to be to a platform top
+
+//where a moving entity was at the start and end of the last simulation
+//step. Transform shows a blend of the two so motion stays smooth when
+//the frame rate and the step rate don't line up.
+struct Interpolated{
+    previous: Vec3,
+    current: Vec3,
+}
+
+pub struct PhysicsPlugin;
+impl Plugin for PhysicsPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_system_to_stage(CoreStage::PreUpdate, restore_positions.system())
+            .add_system_to_stage(SIMULATION, begin_step.exclusive_system().at_start())
+            .add_system_to_stage(SIMULATION, apply_knockback.system().before("body_step"))
+            .add_system_to_stage(SIMULATION, body_step.system().label("body_step"))
+            .add_system_to_stage(SIMULATION, 
+                update_scroll.system().label("update_scroll").after("body_step"))
+            .add_system_to_stage(SIMULATION, 
+                sweep_platforms.system().label("sweep_platforms").after("scroll_world"))
+            .add_system_to_stage(SIMULATION, separate_bodies.system().after("body_step"))
+            .add_system_to_stage(SIMULATION, end_drop_through.system())
+            .add_system_to_stage(CoreStage::PostUpdate, track_bodies.system())
+            .add_system_to_stage(CoreStage::PostUpdate, 
+                interpolate_positions.system()
+                    .label("interpolate_positions")
+                    .before(TransformSystem::TransformPropagate));
+    }
+}
+
+//the player stays in the middle and the world moves past. work out
+//how fast once here so every scroll system agrees.
+fn update_scroll(
+    arena: Res<ArenaLock>,
+    mut scroll: ResMut<WorldScroll>,
+    player_query: Query<(&Velocity, &Contact), With<Player>>,
+    path_query: Query<(&PlatformPath, &Transform)>,
+    ){
+    if let Ok((velocity, contact)) = player_query.single(){
+        if arena.locked{
+            scroll.speed = 0.0;
+        }else{
+            //walking, charging and knockback all push the world the other way
+            scroll.speed = -velocity.velocity.x;
+
+            //riding a moving platform carries the world past him too. the
+            //platform scrolls at its own depth so take that back out.
+            if let Some(platform) = contact.platform{
+                if let Ok((path, transform)) = path_query.get(platform){
+                    scroll.speed -= path.velocity.x / transform.translation.z;
+                }
+            }
+        }
+
+        //a warp moves everything in one step, see ground.rs
+        scroll.speed += scroll.warp / TIMESTEP;
+        scroll.warp = 0.0;
+    }
+}
+
+fn apply_knockback(
+    mut knockback_events: EventReader<Knockback>,
+    mut status_events: EventWriter<ApplyStatus>,
+    mut query: Query<(&mut Transform, &mut Velocity, &mut Gravity)>,
+    ){
+    for knock in knockback_events.iter(){
+        if let Ok((mut transform, mut velocity, mut gravity)) = query.get_mut(knock.target){
+            //always away from whoever did the hitting
+            let dir = if transform.translation.x < knock.from_x{ -1.0 }else{ 1.0 };
+            velocity.velocity.x = dir * knock.force;
+            velocity.velocity.y = knock.force * 0.3;
+            transform.translation.y += 5.0; //get it off platform
+            gravity.falling = true;
+
+            //hit-stun takes control away while the body slides
+            if knock.stun > 0.0{
+                status_events.send(ApplyStatus{
+                    target: knock.target,
+                    kind: StatusKind::Stun,
+                    duration: knock.stun,
+                });
+            }
+        }
+    }
+}
+
+//one step of a body. velocity.x eases toward body.target_vx, slower
+//in the air, and gravity pulls on anything falling. gives back how
+//far it moved. chunks.rs flies test jumps with this too.
+pub(crate) fn integrate(body: &Body, falling: bool, gravity: f32, velocity: &mut Vec3) -> Vec2{
+    let delta_seconds = TIMESTEP;
+    let vx = velocity.x;
+    let control = if falling{ body.air_control }else{ 1.0 };
+    //speeding up the same way uses accel. slowing or turning uses decel.
+    let speeding_up = body.target_vx * vx >= 0.0 && body.target_vx.abs() > vx.abs();
+    let rate = if speeding_up{ body.accel }else{ body.decel };
+    let step = rate * control * delta_seconds;
+    let diff = body.target_vx - vx;
+    velocity.x = if diff.abs() <= step{
+        body.target_vx
+    }else{
+        vx + step * diff.signum()
+    };
+
+    let mut moved = Vec2::new(velocity.x * delta_seconds, 0.0);
+    if falling{
+        moved.y = velocity.y * delta_seconds;
+        velocity.y -= gravity * delta_seconds;
+    }
+    moved
+}
+
+fn body_step(
+    arena: Res<ArenaLock>,
+    tuning: Res<Tuning>,
+    mut query: Query<(&mut Transform, &mut Velocity, &Gravity, &Body, Option<&Player>)>,
+    ){
+    for(mut transform, mut velocity, gravity, body, player) in query.iter_mut(){
+        let moved = integrate(body, gravity.falling, tuning.physics.gravity, &mut velocity.velocity);
+
+        //the player only moves himself when the world can't scroll
+        if player.is_none() || arena.locked{
+            transform.translation.x += moved.x;
+        }
+        transform.translation.y += moved.y;
+    }
+}
+
+//anything that moves gets interpolated. starts out at rest where it spawned.
+fn track_bodies(
+    mut commands: Commands,
+    query: Query<(Entity, &Transform), (With<Velocity>, Without<Interpolated>)>,
+    ){
+    for(entity, transform) in query.iter(){
+        commands.entity(entity).insert(Interpolated{
+            previous: transform.translation,
+            current: transform.translation,
+        });
+    }
+}
+
+//put back the real position before anything else looks at it.
+fn restore_positions(
+    mut query: Query<(&mut Transform, &Interpolated)>,
+    ){
+    for(mut transform, interpolated) in query.iter_mut(){
+        transform.translation = interpolated.current;
+    }
+}
+
+//runs first in every simulation step.
+fn begin_step(world: &mut World){
+    let mut query = world.query::<(&Transform, &mut Interpolated)>();
+    for(transform, mut interpolated) in query.iter_mut(world){
+        interpolated.previous = transform.translation;
+    }
+}
+
+fn interpolate_positions(
+    fixed_timesteps: Res<FixedTimesteps>,
+    state: Res<State<AppState>>,
+    mut query: Query<(&mut Transform, &mut Interpolated)>,
+    ){
+    //with the steps stopped the overstep still moves, so draw
+    //where things are rather than shake between two spots.
+    let alpha = match fixed_timesteps.get(SIMULATION_TIMESTEP){
+        Some(_) if *state.current() != AppState::Playing => 1.0,
+        Some(timestep) => timestep.overstep_percentage() as f32,
+        None => return,
+    };
+    for(mut transform, mut interpolated) in query.iter_mut(){
+        //Transform holds the real position until now. 
+        interpolated.current = transform.translation;
+        if interpolated.current.distance(interpolated.previous) > SNAP_DISTANCE{
+            continue;
+        }
+        transform.translation = interpolated.previous.lerp(interpolated.current, alpha);
+    }
+}
+
+//how far two boxes overlap on x and y. both positive means touching.
+fn overlap(a_pos: Vec3, a_size: Vec2, b_pos: Vec3, b_size: Vec2) -> (f32, f32){
+    let x = (a_size.x + b_size.x) / 2.0 - (a_pos.x - b_pos.x).abs();
+    let y = (a_size.y + b_size.y) / 2.0 - (a_pos.y - b_pos.y).abs();
+    (x, y)
+}
+
+//entry and exit times along one axis of a sweep. None if the boxes
+//never line up on this axis.
+fn axis_times(start: f32, motion: f32, reach: f32, target: f32) -> Option<(f32, f32)>{
+    let near = target - reach - start;
+    let far = target + reach - start;
+    if motion == 0.0{
+        //not moving on this axis, so it has to be inside already
+        if near < 0.0 && far > 0.0{
+            Some((f32::NEG_INFINITY, f32::INFINITY))
+        }else{
+            None
+        }
+    }else{
+        let a = near / motion;
+        let b = far / motion;
+        Some((a.min(b), a.max(b)))
+    }
+}
+
+//moves a point from start by motion against a box of half size reach.
+//returns how far along (0.0 - 1.0) it first touches and which side of 
+//the box it hit. boxes that already overlap don't count.
+fn sweep(start: Vec2, motion: Vec2, reach: Vec2, target: Vec2) -> Option<(f32, Side)>{
+    let (entry_x, exit_x) = axis_times(start.x, motion.x, reach.x, target.x)?;
+    let (entry_y, exit_y) = axis_times(start.y, motion.y, reach.y, target.y)?;
+    let entry = entry_x.max(entry_y);
+    let exit = exit_x.min(exit_y);
+    if entry > exit || entry < 0.0 || entry > 1.0{
+        return None;
+    }
+    let side = if entry_x > entry_y{
+        if motion.x > 0.0{ Side::Left }else{ Side::Right }
+    }else{
+        if motion.y < 0.0{ Side::Top }else{ Side::Bottom }
+    };
+    Some((entry, side))
+}
+
+//a platform as the sweep sees it, where it was when the step
+//started and where it is now.
+struct SweepTarget{
+    then: Vec2,
+    now: Vec2,
+    size: Vec2,
+    one_way: bool,
+}
+
+//when (0.0 - 1.0) a body of size going from start to end in one step
+//first touches the platform, the side it hit and where the body stops.
+//done relative to the platform, so one that moved in the step counts.
+fn time_of_impact(start: Vec2, end: Vec2, size: Vec2, platform: &SweepTarget) -> Option<(f32, Side, Vec2)>{
+    let rel_start = start - (platform.then - platform.now);
+    let motion = end - rel_start;
+    let reach = (size + platform.size) / 2.0;
+    let (time, side) = sweep(rel_start, motion, reach, platform.now)?;
+    //one-way platforms only stop a body landing on them from above
+    if platform.one_way && side != Side::Top{
+        return None;
+    }
+    Some((time, side, rel_start + motion * time))
+}
+
+//feet resting on the top of a platform
+fn standing_on(pos: Vec3, size: Vec2, platform_pos: Vec3, platform_size: Vec2) -> bool{
+    let feet = pos.y - size.y / 2.0;
+    let top = platform_pos.y + platform_size.y / 2.0;
+    let (x, _) = overlap(pos, size, platform_pos, platform_size);
+    x > 0.0 && (feet - top).abs() <= GROUND_PROBE
+}
+
+//runs after everything has moved. each body is swept from where it 
+//started the step to where it is now, so a fast fall can't skip a
+//platform. platforms scroll too, so the sweep is done relative to them.
+fn sweep_platforms(
+    arena: Res<ArenaLock>,
+    mut landed_events: EventWriter<Landed>,
+    mut body_query: Query<(Entity, &mut Transform, &mut Velocity, &StrikeBox, &mut Contact,
+                           Option<&Interpolated>, Option<&DropThrough>, Option<&Player>), 
+                          Without<Platform>>,
+    platform_query: Query<(Entity, &Transform, &Sprite, Option<&Interpolated>, 
+                           Option<&PlatformKind>), With<Platform>>,
+    ){
+    for(entity, mut body_tf, mut velocity, strike_box, mut contact, 
+        body_interp, dropping, player) in body_query.iter_mut(){
+        let size = Vec2::new(strike_box.h, strike_box.w);
+        let end = body_tf.translation.truncate();
+        //teleports don't sweep, they just start over where they land
+        let start = match body_interp{
+            Some(interpolated) if interpolated.previous.distance(body_tf.translation) <= SNAP_DISTANCE
+                => interpolated.previous.truncate(),
+            _ => end,
+        };
+
+        //only the first platform hit counts
+        let mut first: Option<(f32, Side, Vec2)> = None;
+        for(_, platform_tf, platform_sprite, platform_interp, kind) in platform_query.iter(){
+            let one_way = match kind{
+                Some(PlatformKind::OneWay) => true,
+                _ => false,
+            };
+            if one_way && dropping.is_some(){
+                continue;
+            }
+            let now = platform_tf.translation.truncate();
+            let then = match platform_interp{
+                Some(interpolated) if interpolated.previous.distance(platform_tf.translation) <= SNAP_DISTANCE
+                    => interpolated.previous.truncate(),
+                _ => now,
+            };
+            let platform = SweepTarget{
+                then,
+                now,
+                size: platform_sprite.size,
+                one_way,
+            };
+            if let Some((time, side, hit)) = time_of_impact(start, end, size, &platform){
+                let sooner = match first{
+                    Some((best, _, _)) => time < best,
+                    None => true,
+                };
+                if sooner{
+                    first = Some((time, side, hit));
+                }
+            }
+        }
+
+        contact.side = None;
+        if let Some((_, side, hit)) = first{
+            contact.side = Some(side);
+            match side{
+                Side::Top => {
+                    //sit on the surface and lose the fall speed so the
+                    //next jump starts clean
+                    body_tf.translation.y = hit.y;
+                    if velocity.velocity.y < 0.0{
+                        landed_events.send(Landed{
+                            entity,
+                            impact_speed: -velocity.velocity.y,
+                        });
+                    }
+                    velocity.velocity.y = 0.0;
+                }
+                Side::Bottom => {
+                    //bumped his head
+                    body_tf.translation.y = hit.y;
+                    velocity.velocity.y = velocity.velocity.y.min(0.0);
+                }
+                Side::Left | Side::Right => {
+                    velocity.velocity.x = 0.0;
+                    //the world stops for the player instead
+                    if player.is_none() || arena.locked{
+                        body_tf.translation.x = hit.x;
+                    }
+                }
+            }
+        }
+
+        contact.platform = None;
+        for(platform, platform_tf, platform_sprite, _, kind) in platform_query.iter(){
+            if dropping.is_some(){
+                if let Some(PlatformKind::OneWay) = kind{
+                    continue;
+                }
+            }
+            if standing_on(body_tf.translation, size, platform_tf.translation, platform_sprite.size){
+                contact.platform = Some(platform);
+                break;
+            }
+        }
+        contact.ground = contact.platform.is_some();
+    }
+}
+
+fn end_drop_through(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut DropThrough)>,
+    ){
+    for(entity, mut drop) in query.iter_mut(){
+        drop.timer.tick(std::time::Duration::from_secs_f32(TIMESTEP));
+        if drop.timer.finished(){
+            commands.entity(entity).remove::<DropThrough>();
+        }
+    }
+}
+
+fn separate_bodies(
+    arena: Res<ArenaLock>,
+    mut query: Query<(Entity, &mut Transform, &mut Velocity, &StrikeBox,
+                      Option<&StatusEffects>, Option<&Player>), With<Gravity>>,
+    ){
+    //copied out first, the loop below moves them
+    let bodies: Vec<(Entity, Vec3, Vec2)> = query.iter_mut()
+        .map(|(entity, transform, _, strike_box, _, _)|
+             (entity, transform.translation, Vec2::new(strike_box.h, strike_box.w)))
+        .collect();
+
+    //only knocked bodies get resolved. they stop when they run into another.
+    //walking bodies have to overlap or melee attacks would never land.
+    for(entity, mut transform, mut velocity, strike_box, status, player) in query.iter_mut(){
+        let knocked = match status{
+            Some(status) => status.locks_input(),
+            None => false,
+        };
+        let vx = velocity.velocity.x;
+        if vx == 0.0 || !knocked{
+            continue;
+        }
+        let size = Vec2::new(strike_box.h, strike_box.w);
+        for(other, other_pos, other_size) in bodies.iter(){
+            if *other == entity{
+                continue;
+            }
+            let (x, y) = overlap(transform.translation, size, *other_pos, *other_size);
+            if x <= 0.0 || y <= 0.0{
+                continue;
+            }
+            let side = (transform.translation.x - other_pos.x).signum();
+            if vx * side < 0.0{
+                velocity.velocity.x = 0.0;
+                if player.is_none() || arena.locked{
+                    transform.translation.x += side * x;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //Vec2::new isn't const in bevy's glam
+    fn body() -> Vec2{
+        Vec2::new(40.0, 60.0)
+    }
+
+    fn still(at: Vec2, size: Vec2) -> SweepTarget{
+        SweepTarget{
+            then: at,
+            now: at,
+            size,
+            one_way: false,
+        }
+    }
+
+    #[test]
+    fn fast_fall_lands_on_a_thin_platform(){
+        //200 px in one step, the platform is only 30 thick
+        let platform = still(Vec2::ZERO, Vec2::new(200.0, 30.0));
+        let (time, side, hit) = time_of_impact(Vec2::new(0.0, 100.0), Vec2::new(0.0, -100.0), body(), &platform)
+            .expect("fell through");
+        assert!(side == Side::Top);
+        assert!((time - 0.275).abs() < 1e-4);
+        assert!((hit.y - 45.0).abs() < 1e-3); //feet on the top
+    }
+
+    #[test]
+    fn walking_into_the_side(){
+        let platform = still(Vec2::ZERO, Vec2::new(200.0, 30.0));
+        let (time, side, hit) = time_of_impact(Vec2::new(-200.0, 0.0), Vec2::new(-100.0, 0.0), body(), &platform)
+            .expect("walked through");
+        assert!(side == Side::Left);
+        assert!((time - 0.8).abs() < 1e-4);
+        assert!((hit.x + 120.0).abs() < 1e-3);
+
+        //a one-way platform lets him through
+        let one_way = SweepTarget{ one_way: true, ..platform };
+        assert!(time_of_impact(Vec2::new(-200.0, 0.0), Vec2::new(-100.0, 0.0), body(), &one_way).is_none());
+    }
+
+    #[test]
+    fn platform_rising_under_a_body(){
+        //he stands still, the platform comes up 30 into his feet
+        let platform = SweepTarget{
+            then: Vec2::new(0.0, -20.0),
+            now: Vec2::new(0.0, 10.0),
+            size: Vec2::new(200.0, 30.0),
+            one_way: false,
+        };
+        let at = Vec2::new(0.0, 50.0);
+        let (_, side, hit) = time_of_impact(at, at, body(), &platform).expect("platform went through him");
+        assert!(side == Side::Top);
+        assert!((hit.y - 55.0).abs() < 1e-3); //carried up on top of it
+    }
+
+    #[test]
+    fn already_overlapping_is_no_hit(){
+        let platform = still(Vec2::ZERO, Vec2::new(200.0, 30.0));
+        assert!(time_of_impact(Vec2::new(0.0, 10.0), Vec2::new(0.0, 0.0), body(), &platform).is_none());
+    }
+
+    #[test]
+    fn missing_it_is_no_hit(){
+        let platform = still(Vec2::ZERO, Vec2::new(200.0, 30.0));
+        assert!(time_of_impact(Vec2::new(300.0, 100.0), Vec2::new(300.0, -100.0), body(), &platform).is_none());
+    }
+}
@@ -1,166 +1,250 @@
 
 use bevy::prelude::*;
-use bevy::sprite::collide_aabb::collide;
-
-use crate::{SCALE_UP, Materials,  Direction, WinSize, Platform, Gravity, 
-    Player, StrikeBox, PlayerAction, SPEEDSTOP, SPEEDFAST, SPEEDSLOW, 
-    Velocity, Enemy, Proximity,};
-
-const MAX_HEIGHT: f32 = 60.0;//added from bottom of screen
+use serde::Deserialize;
+
+use crate::{Platform, Gravity, Player, PlayerAction,
+    Velocity, Enemy, Proximity, WorldScroll, Contact, Side, PlatformKind, PlatformPath, ArenaLock,
+    RestartRun, SIMULATION, TIMESTEP};
+use crate::registry::AssetRegistry;
+use crate::ground::GroundTile;
+
+//a crumbling platform that has been stood on falls apart when
+//the timer runs out.
+struct Crumble{
+    timer: Timer,
+    triggered: bool,
+}
 
 pub struct PlatformsPlugin;
 impl Plugin for PlatformsPlugin{
     fn build(&self, app: &mut AppBuilder){
         app
-            .add_startup_stage(
-                "platforms", 
-                SystemStage::single(platform_spawn.system(),)
-                  )
-            .add_system(touching_platform_player.system())
-            .add_system(touching_platform_enemy.system())
-            .add_system(scroll_platform.system());
+            .add_system_to_stage(SIMULATION, touching_platform_player.system()
+                .label("touching_platform").after("sweep_platforms"))
+            .add_system_to_stage(SIMULATION, touching_platform_enemy.system()
+                .label("touching_platform").after("sweep_platforms"))
+            .add_system_to_stage(SIMULATION, move_platforms.system()
+                .label("move_platforms").label("scroll_world").after("update_scroll"))
+            .add_system_to_stage(SIMULATION, carry_riders.system()
+                .label("scroll_world").after("move_platforms"))
+            .add_system_to_stage(SIMULATION, crumble_platforms.system().after("sweep_platforms"))
+            .add_system_to_stage(SIMULATION, bounce_pads.system().after("touching_platform"))
+            .add_system_to_stage(SIMULATION, 
+                scroll_platform.system().label("scroll_world").after("update_scroll"))
+            .add_system(clear_platforms.system());
     }
 }
 
-fn platform_spawn(
-    mut commands: Commands,
-    //mut materials: ResMut<Assets<ColorMaterial>>,
-    materials: Res<Materials>,
-    windows: Res<WinSize>,
-    ){
-    let screen_bottom = -(windows.h/2.0);
-    let screen_width = windows.w;
-    //this first platform is the ground. It stays under the player always.
-    commands
-        .spawn_bundle(SpriteBundle{
-            material: materials.pl_01.clone(),
-            sprite: Sprite::new(Vec2::new(screen_width, 30.0)),
-            transform: Transform{
-                //z translation is 0.0 to keep hidden. Not working right.   
-                translation: Vec3::new(0.0, screen_bottom - 5.0, 0.0),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Platform);
-
-    commands
-        .spawn_bundle(SpriteBundle{
-            sprite: Sprite::new(Vec2::new(90.0, 30.0)), 
-            transform: Transform{
-                translation: Vec3::new(300.0, screen_bottom + MAX_HEIGHT, 1.9),
-                ..Default::default()
-            },
+//one entry per platform in the level file. x is where it starts in 
+//the world, y is how far above the bottom of the screen.
+#[derive(Deserialize, PartialEq, Debug)]
+pub(crate) struct PlatformSpec{
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) w: f32,
+    pub(crate) h: f32,
+    pub(crate) kind: PlatformKind,
+    #[serde(default)]
+    pub(crate) hidden: bool, //collision only, the art is drawn some other way
+}
+
+//platforms come from the level file. see level.rs. the ground is in ground.rs
+pub(crate) fn spawn_platform(
+    commands: &mut Commands,
+    registry: &AssetRegistry,
+    screen_bottom: f32,
+    spec: &PlatformSpec,
+    ) -> Entity{
+    let material = match spec.kind{
+        PlatformKind::Solid => registry.material("pl_01"),
+        PlatformKind::OneWay => registry.material("pl_oneway"),
+        PlatformKind::Moving{..} => registry.material("pl_moving"),
+        PlatformKind::Crumbling{..} => registry.material("pl_crumble"),
+        PlatformKind::Bounce{..} => registry.material("pl_bounce"),
+    };
+    let mut platform = commands.spawn_bundle(SpriteBundle{
+        material,
+        sprite: Sprite::new(Vec2::new(spec.w, spec.h)), 
+        transform: Transform{
+            translation: Vec3::new(spec.x, screen_bottom + spec.y, 1.9),
             ..Default::default()
-        })
+        },
+        visible: Visible{
+            is_visible: !spec.hidden,
+            is_transparent: true,
+        },
+        ..Default::default()
+    });
+    platform
         .insert(Platform)
+        .insert(spec.kind.clone())
         .insert(Velocity{
             velocity: Vec3::new(1.0, 0.0, 0.0),    
         });
+
+    match &spec.kind{
+        PlatformKind::Moving{path, speed} => {
+            platform.insert(PlatformPath{
+                points: path.clone(),
+                speed: *speed,
+                next: 0,
+                offset: Vec2::ZERO,
+                velocity: Vec2::ZERO,
+            });
+        }
+        PlatformKind::Crumbling{delay} => {
+            platform.insert(Crumble{
+                timer: Timer::from_seconds(*delay, false),
+                triggered: false,
+            });
+        }
+        _ => {}
+    }
+    platform.id()
 }
 
+//physics.rs sweeps the bodies and reports what they hit.
 fn touching_platform_player(
-    mut commands: Commands,
-    mut player_query: Query<(
-        &mut Player, &Transform,  &TextureAtlasSprite, &StrikeBox, &mut Gravity)>,
-    mut platform_query: Query<(Entity,  &Transform, &Sprite), With<Platform>>,
+    mut player_query: Query<(&mut Player, &Contact, &mut Gravity)>,
     ){
+    for(mut player, contact, mut gravity) in player_query.iter_mut(){
+        match contact.side{
+            Some(Side::Left) | Some(Side::Right) => {
+                player.action = PlayerAction::Bumped;
+            }
+            _ => {}
+        }
+        gravity.falling = !contact.ground;
+    }
+}
 
-    let mut on_something = false;
-
-    for(mut player, player_tf, player_sprite, strike_box, mut gravity) in player_query.iter_mut(){
-        for(platform_entity, platform_tf, platform_sprite) in platform_query.iter(){
-
-            let player_size= Vec2::new(strike_box.h, strike_box.w);
-            //let player_size= Vec2::new(40.0, 115.0);
-
-            let collision = collide(
-                platform_tf.translation,
-                platform_sprite.size,
-                player_tf.translation,
-                player_size,
-               );
-            
 
-            if let Some(_) = collision{
-                if player_tf.translation.y - player_size.y/2.0 + 5.0 > platform_tf.translation.y{
-                    on_something = true;
-                }
-                else{
-                    player.action = PlayerAction::Bumped;
-                }
+fn touching_platform_enemy(
+    mut enemy_query: Query<(&mut Enemy, &Contact, &mut Gravity, &Proximity)>,
+    ){
+    for(mut enemy, contact, mut gravity, proximity) in enemy_query.iter_mut(){
+        match contact.side{
+            Some(Side::Left) | Some(Side::Right) => {
+                enemy.action = PlayerAction::Stand;
             }
-        };
-        if on_something{
+            _ => {}
+        }
+        //enemies off screen hold still until the player gets close.
+        if contact.ground || proximity.near_player == false{
             gravity.falling = false;
-        }else{
+        }else if proximity.near_player == true{
             gravity.falling = true;
         }
     }
 }
 
-
-fn touching_platform_enemy(
-    mut commands: Commands,
-    mut enemy_query: Query<(
-        &mut Enemy, &Transform,   &StrikeBox, &mut Gravity, &mut Proximity)>,
-    mut platform_query: Query<(Entity,  &Transform, &Sprite), With<Platform>>,
+fn scroll_platform(
+    scroll: Res<WorldScroll>,
+    mut bg_query: Query<(&mut Transform, &Velocity), (With<Platform>, Without<GroundTile>)>,
     ){
+    //layers will be divided by value in transform.z.  
+    //the further back z (lower) the slower the velocity. 
+    for (mut transform, velocity) in bg_query.iter_mut(){
+        //multiplyer is the z value of the background. The further back
+        //into the background, the slower the multiplier.
+        let multiplier = transform.translation.z;
+        transform.translation += 
+            (scroll.speed * multiplier) * velocity.velocity * TIMESTEP;
+    }
+}
 
-    let mut on_something = false;
-
-    for(mut enemy, enemy_tf,  strike_box, mut gravity, mut proximity) in enemy_query.iter_mut(){
-        for(platform_entity, platform_tf, platform_sprite) in platform_query.iter(){
+//moving platforms walk their path on top of the normal scroll.
+fn move_platforms(
+    mut query: Query<(&mut PlatformPath, &mut Transform)>,
+    ){
+    for(mut path, mut transform) in query.iter_mut(){
+        if path.points.is_empty(){
+            continue;
+        }
+        let target = path.points[path.next];
+        let to_target = target - path.offset;
+        let step = path.speed * TIMESTEP;
+        let delta = if to_target.length() <= step{
+            path.next = (path.next + 1) % path.points.len();
+            to_target
+        }else{
+            to_target.normalize() * step
+        };
+        path.offset += delta;
+        path.velocity = delta / TIMESTEP;
+        transform.translation += delta.extend(0.0);
+    }
+}
 
-            let enemy_size= Vec2::new(strike_box.h, strike_box.w);
-            //let player_size= Vec2::new(40.0, 115.0);
+//bodies standing on a moving platform move with it. the player is 
+//carried by the world scroll instead unless the arena is locked.
+fn carry_riders(
+    arena: Res<ArenaLock>,
+    mut rider_query: Query<(&Contact, &mut Transform, Option<&Player>), Without<PlatformPath>>,
+    path_query: Query<&PlatformPath>,
+    ){
+    for(contact, mut transform, player) in rider_query.iter_mut(){
+        if let Some(platform) = contact.platform{
+            if let Ok(path) = path_query.get(platform){
+                if player.is_none() || arena.locked{
+                    transform.translation.x += path.velocity.x * TIMESTEP;
+                }
+                transform.translation.y += path.velocity.y * TIMESTEP;
+            }
+        }
+    }
+}
 
-            let collision = collide(
-                platform_tf.translation,
-                platform_sprite.size,
-                enemy_tf.translation,
-                enemy_size,
-               );
-            
+fn crumble_platforms(
+    mut commands: Commands,
+    contact_query: Query<&Contact>,
+    mut platform_query: Query<(Entity, &mut Crumble)>,
+    ){
+    for contact in contact_query.iter(){
+        if let Some(platform) = contact.platform{
+            if let Ok((_, mut crumble)) = platform_query.get_mut(platform){
+                crumble.triggered = true;
+            }
+        }
+    }
+    for(entity, mut crumble) in platform_query.iter_mut(){
+        if crumble.triggered{
+            crumble.timer.tick(std::time::Duration::from_secs_f32(TIMESTEP));
+            if crumble.timer.finished(){
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
 
-            if let Some(_) = collision{
-                if enemy_tf.translation.y - enemy_size.y/2.0 + 5.0 > platform_tf.translation.y{
-                    on_something = true;
-                }
-                else{
-                    enemy.action = PlayerAction::Stand;
-                }
+//landing on a bounce pad throws the body back up.
+fn bounce_pads(
+    mut body_query: Query<(&Contact, &mut Transform, &mut Velocity, &mut Gravity)>,
+    kind_query: Query<&PlatformKind>,
+    ){
+    for(contact, mut transform, mut velocity, mut gravity) in body_query.iter_mut(){
+        if contact.side != Some(Side::Top){
+            continue;
+        }
+        if let Some(platform) = contact.platform{
+            if let Ok(PlatformKind::Bounce{speed}) = kind_query.get(platform){
+                transform.translation.y += 1.0; //get it off platform
+                velocity.velocity.y = *speed;
+                gravity.falling = true;
             }
-        };
-        if on_something || proximity.near_player == false{
-            gravity.falling = false;
-        }else if proximity.near_player == true{
-            gravity.falling = true;
         }
     }
 }
 
-fn scroll_platform(
-    time: Res<Time>,
-    mut bg_query: Query<(&mut Transform, &Velocity),With<Platform>>,
-    mut player_query: Query<(&Player)>,
+//the ground stays, it is laid out again from the level's pits.
+fn clear_platforms(
+    mut commands: Commands,
+    mut restart_events: EventReader<RestartRun>,
+    query: Query<Entity, (With<Platform>, Without<GroundTile>)>,
     ){
-    if let Ok((player)) = player_query.single_mut(){
-        //layers will be divided by value in transform.z.  
-        //the further back z (lower) the slower the velocity. 
-        let dir = match player.direction{
-            Direction::Right => -1.0,
-            Direction::Left => 1.0,
-            //Direction::NotMoving => 0.0,
-            _ => 0.0,
-        };
-        let vel = dir * player.vel_mod;
-        for (mut transform, velocity) in bg_query.iter_mut(){
-            //multiplyer is the z value of the background. The further back
-            //into the background, the slower the multiplier.
-            let multiplier = transform.translation.z;
-            transform.translation += 
-                (vel * multiplier) * velocity.velocity * time.delta_seconds();
+    for _ in restart_events.iter(){
+        for entity in query.iter(){
+            commands.entity(entity).despawn();
         }
     }
 }
@@ -1,8 +1,20 @@
 use bevy::prelude::*;
 
-use crate::{SCALE_UP, Materials,  Direction, Platform, Velocity, Gravity, 
+use crate::{SCALE_UP, Direction, Velocity, Gravity, Body, Contact, 
     Player, StrikeBox, PlayerAction, SPEEDSTOP, SPEEDFAST, 
-    Attacking, SPEEDMED, SPEEDSLOW};
+    Attacking, Energy, SPEEDSLOW, ArenaLock, WinSize, Knockback,
+    Landed, Damage, DamageKind, DropThrough, PlatformKind, AppState, RestartRun,
+    SIMULATION};
+use crate::registry::AssetRegistry;
+use crate::tuning::Tuning;
+use crate::status::{StatusEffects, slowed};
+use crate::sound::{PlayCue, Cue};
+use crate::stamina::{Stamina, CHARGE_STAMINA};
+
+const LAND_SOUND_SPEED: f32 = 200.0; //softer landings are silent
+pub(crate) const JUMP_LIFT: f32 = 10.0; //a jump starts this far up, off the platform
+const FALL_DAMAGE_SPEED: f32 = 600.0;
+const DROP_THROUGH_TIME: f32 = 0.3; //long enough to clear a one-way platform
 
 
 pub struct PlayersPlugin;
@@ -13,19 +25,35 @@ impl Plugin for PlayersPlugin{
                 "player", 
                 SystemStage::single(player_spawn.system(),)
                   )
-            .add_system(input_player.system())
-            .add_system(animate_player.system())
-            .add_system(control_player.system())
-            .add_system(attacking_player.system());
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(input_player.system())
+                    .with_system(animate_player.system())
+                    .with_system(update_current_x.system())
+                    .with_system(attacking_player.system())
+                       )
+            .add_system_to_stage(SIMULATION, control_player.system().before("body_step"))
+            .add_system_to_stage(SIMULATION,
+                land_player.system().label("deal_damage").after("sweep_platforms"))
+            .add_system(restart_player.system());
     }
 }
 fn player_spawn(
     mut commands: Commands,
-    materials: Res<Materials>,
+    registry: Res<AssetRegistry>,
+    tuning: Res<Tuning>,
+    ){
+    spawn_player(&mut commands, &registry, &tuning);
+}
+
+fn spawn_player(
+    commands: &mut Commands,
+    registry: &AssetRegistry,
+    tuning: &Tuning,
     ){
     commands
         .spawn_bundle(SpriteSheetBundle{
-            texture_atlas: materials.player_sprite.clone(),
+            texture_atlas: registry.atlas("player"),
             transform: Transform{
                 translation: Vec3::new(0.0, 0.0, 1.8),
                 scale: Vec3::new(SCALE_UP, SCALE_UP, 0.0),
@@ -39,13 +67,19 @@ fn player_spawn(
             vel_mod: SPEEDSTOP,
             current_x: 0.0,
         })
-        .insert(Timer::from_seconds(0.1, true))
+        .insert(Timer::from_seconds(tuning.player_animation.frame_time, true))
         .insert(Velocity{
             velocity: Vec3::new(0.0, 0.0, 0.0),
         })
         .insert(Gravity{
             falling: true,
         })
+        .insert(Contact{
+            ground: false,
+            side: None,
+            platform: None,
+        })
+        .insert(tuning.physics.player_body.body())
         .insert(StrikeBox{
             h: 40.0,
             w: 115.0,
@@ -54,8 +88,30 @@ fn player_spawn(
         })
         .insert(Attacking{
             attack: false,
-        });
+        })
+        .insert(Energy{
+            power: 100,
+            max: 100,
+        })
+        .insert(Stamina::full(100.0))
+        .insert(StatusEffects::new());
+
+}
 
+//a fresh player back at the start.
+fn restart_player(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    tuning: Res<Tuning>,
+    mut restart_events: EventReader<RestartRun>,
+    query: Query<Entity, With<Player>>,
+    ){
+    for _ in restart_events.iter(){
+        for entity in query.iter(){
+            commands.entity(entity).despawn();
+        }
+        spawn_player(&mut commands, &registry, &tuning);
+    }
 }
 
 fn update_current_x(
@@ -74,17 +130,17 @@ fn update_current_x(
 
 fn animate_player(
     time: Res<Time>,
-    texture_atlases: Res<Assets<TextureAtlas>>,
+    tuning: Res<Tuning>,
     mut query: Query<(
-        &mut Timer, &mut TextureAtlasSprite, 
-        &Handle<TextureAtlas>, &mut Player, &mut Transform,
+        &mut Timer, &mut TextureAtlasSprite, &mut Player, &mut Transform,
              )>,
              ){
 
-    for(mut timer, mut sprite, texture_atlas_handle, mut player, mut transform) in query.iter_mut(){
+    //the frames are in the tuning file
+    let frames = &tuning.player_animation;
+    for(mut timer, mut sprite, mut player, mut transform) in query.iter_mut(){
         timer.tick(time.delta());
         if timer.finished(){
-            let texture_atlas = texture_atlases.get(texture_atlas_handle).unwrap();
             
             //is player going left or right? 
             if player.direction == Direction::Left{
@@ -96,71 +152,41 @@ fn animate_player(
 
             match player.action{
                 PlayerAction::Walk => {
-                    match sprite.index{
-                        18 => sprite.index = 19,
-                        19 => sprite.index = 20,
-                        20 => sprite.index = 21,
-                        _ => sprite.index = 18,
-                    }
+                    sprite.index = frames.walk.next(sprite.index);
                 }
                 
                 PlayerAction::Charge =>{
-                    match sprite.index{
-                        14 => sprite.index = 15,
-                        15 => sprite.index = 16,
-                        16 => sprite.index = 17,
-                        _ => sprite.index = 14,
-                    } 
-                    if sprite.index == 17{
+                    sprite.index = frames.charge.next(sprite.index);
+                    if sprite.index == frames.charge.last{
                         player.vel_mod = SPEEDSTOP;
                         player.action = PlayerAction::Stand;
                     }
                 }
 
                 PlayerAction::Chop =>{
-                    match sprite.index{
-                        27 => sprite.index = 28,
-                        28 => sprite.index = 29,
-                        29 => sprite.index = 30, 
-                        _ => sprite.index = 27,
-                    } 
-                    if sprite.index == 30{
+                    sprite.index = frames.chop.next(sprite.index);
+                    if sprite.index == frames.chop.last{
                         player.vel_mod = SPEEDSTOP;
                         player.action = PlayerAction::Stand;
                     }
                 }
 
                 PlayerAction::Block =>{
-                    match sprite.index{
-                        23 => sprite.index = 24,
-                        24 => sprite.index = 25,
-                        25 => sprite.index = 26,
-                        26 => sprite.index = 25, //hold until released.
-                        _ => sprite.index = 23,
-                    }
+                    //holds until released
+                    sprite.index = frames.block.next(sprite.index);
                 }
 
                 PlayerAction::Fly => {
-                    match sprite.index{
-                        23 => sprite.index = 24,
-                        24 => sprite.index = 25,
-                        25 => sprite.index = 26,
-                        _ => sprite.index = 23,
-                    }
-                    if sprite.index == 26 {
+                    sprite.index = frames.jump.next(sprite.index);
+                    if sprite.index == frames.jump.last {
                         player.vel_mod = SPEEDSTOP;
                         player.action = PlayerAction::Stand;
                     }
                 }
 
                 PlayerAction::Jump => {
-                    match sprite.index{
-                        23 => sprite.index = 24,
-                        24 => sprite.index = 25,
-                        25 => sprite.index = 26,
-                        _ => sprite.index = 23,
-                    }
-                    if sprite.index == 26 {
+                    sprite.index = frames.jump.next(sprite.index);
+                    if sprite.index == frames.jump.last {
                         if player.vel_mod == SPEEDSLOW{
                             player.action = PlayerAction::Walk;
                         }else{
@@ -171,7 +197,7 @@ fn animate_player(
                     }
                 }
                 _ => {
-                    sprite.index = 18
+                    sprite.index = frames.stand
                 }
             }
         }
@@ -179,44 +205,67 @@ fn animate_player(
 }
 
 fn control_player(
-    time: Res<Time>,
-    mut query: Query<(&mut Player, &mut Transform, 
-                      &mut Velocity, &mut Gravity)>,
+    arena: Res<ArenaLock>,
+    tuning: Res<Tuning>,
+    window: Res<WinSize>,
+    mut knockback_events: EventWriter<Knockback>,
+    mut cue_events: EventWriter<PlayCue>,
+    mut query: Query<(Entity, &mut Player, &mut Transform, 
+                      &mut Velocity, &mut Gravity, &mut Body, &StatusEffects)>,
     ){
-    if let Ok((mut player, mut transform, 
-               mut player_velocity, mut player_gravity)) = query.single_mut(){
-
-        let delta_seconds = f32::min(0.3, time.delta_seconds());
+    if let Ok((entity, mut player, mut transform, 
+               mut player_velocity, mut player_gravity, mut body, status)) = query.single_mut(){
+
+        //walking and charging are just a speed to aim for.
+        //physics.rs does the speeding up and slowing down.
+        let dir = match player.direction{
+            Direction::Right => 1.0,
+            Direction::Left => -1.0,
+            _ => 0.0,
+        };
+        body.target_vx = dir * slowed(player.vel_mod, Some(status));
+
+        //the world can't scroll in a locked arena so the player
+        //walks across the screen instead. stay inside the edges.
+        if arena.locked{
+            let edge = window.w / 2.0 - 40.0;
+            transform.translation.x = transform.translation.x.max(-edge).min(edge);
+        }
 
         match player.action{
             PlayerAction::Jump =>{
                     if player_gravity.falling == false{
-                    transform.translation.y += 10.0; //get it off plaform
-                    player_velocity.velocity.y = 333.0; //initial up velocity 
+                    transform.translation.y += JUMP_LIFT;
+                    player_velocity.velocity.y = tuning.physics.jump_speed; //initial up velocity 
                     player_gravity.falling = true;
+                    cue_events.send(PlayCue{
+                        cue: Cue::Jump,
+                        from_x: None,
+                    });
                     //player.action = PlayerAction::Stand;
                     }
             }
             PlayerAction::Chop =>{
                     if player_gravity.falling == false{
-                    transform.translation.y += 10.0; //get it off plaform
-                    player_velocity.velocity.y = 222.0; //initial up velocity 
+                    transform.translation.y += JUMP_LIFT;
+                    player_velocity.velocity.y = tuning.physics.chop_hop; //initial up velocity 
                     player_gravity.falling = true;
                     //player.action = PlayerAction::Stand;
                     }
             }
             PlayerAction::Bumped => {
-                transform.translation.y += 10.0;//get him off platform
-                player_velocity.velocity.y = 30.0;
-                player_gravity.falling = true;
-                if player.direction == Direction::Left{
-                    player.direction = Direction::Right;
-                    transform.translation.x += 10.0; //get it off platform
-                }else{
-                    player.direction = Direction::Left;
-                    transform.translation.x -= 10.0; //get it off platform
-                }
-                player.vel_mod = SPEEDMED;
+                //bounce back off whatever is in front of him
+                let facing = match player.direction{
+                    Direction::Left => -1.0,
+                    _ => 1.0,
+                };
+                knockback_events.send(Knockback{
+                    target: entity,
+                    from_x: transform.translation.x + facing,
+                    force: 250.0,
+                    stun: 0.2,
+                });
+                player.vel_mod = SPEEDSTOP;
                 player.action = PlayerAction::Fly;
             }
 
@@ -227,6 +276,35 @@ fn control_player(
     }
 }
 
+fn land_player(
+    mut cue_events: EventWriter<PlayCue>,
+    mut landed_events: EventReader<Landed>,
+    mut damage_events: EventWriter<Damage>,
+    query: Query<Entity, With<Player>>,
+    ){
+    if let Ok(player_entity) = query.single(){
+        for landed in landed_events.iter(){
+            if landed.entity != player_entity{
+                continue;
+            }
+            if landed.impact_speed > LAND_SOUND_SPEED{
+                cue_events.send(PlayCue{
+                    cue: Cue::Land,
+                    from_x: None,
+                });
+            }
+            //long drops hurt. a normal jump never gets close.
+            if landed.impact_speed > FALL_DAMAGE_SPEED{
+                damage_events.send(Damage{
+                    target: player_entity,
+                    amount: ((landed.impact_speed - FALL_DAMAGE_SPEED) / 10.0) as i32,
+                    kind: DamageKind::Normal,
+                });
+            }
+        }
+    }
+}
+
 fn attacking_player(
     mut query: Query<(&mut Attacking, &Player)>,
     ){
@@ -241,12 +319,19 @@ fn attacking_player(
 
 
 fn input_player(
+    mut commands: Commands,
+    mut cue_events: EventWriter<PlayCue>,
     keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&mut Player)>,
+    mut query: Query<(Entity, &mut Player, &mut Stamina, &StatusEffects, &Contact)>,
+    kind_query: Query<&PlatformKind>,
     ){
 
 
-    if let Ok((mut player)) = query.single_mut(){
+    if let Ok((entity, mut player, mut stamina, status, contact)) = query.single_mut(){
+        //stunned or knocked down, no control
+        if status.locks_input(){
+            return;
+        }
 
         if keyboard_input.just_pressed(KeyCode::Left){
             player.direction = Direction::Left;
@@ -270,9 +355,14 @@ fn input_player(
             player.vel_mod = SPEEDSTOP;
         }
 
-        if keyboard_input.just_pressed(KeyCode::R){
+        //too tired to charge
+        if keyboard_input.just_pressed(KeyCode::R) && stamina.spend(CHARGE_STAMINA){
             player.vel_mod = SPEEDFAST;
             player.action = PlayerAction::Charge;
+            cue_events.send(PlayCue{
+                cue: Cue::Charge,
+                from_x: None,
+            });
         }
 
         if keyboard_input.just_pressed(KeyCode::E){
@@ -290,7 +380,17 @@ fn input_player(
             player.action = PlayerAction::Stand;
         }
 
-        if keyboard_input.just_pressed(KeyCode::Space){
+        //Down+Jump drops through a one-way platform instead of jumping.
+        //off anything else it is just a jump.
+        let on_one_way = match contact.platform.map(|platform| kind_query.get(platform)){
+            Some(Ok(PlatformKind::OneWay)) => true,
+            _ => false,
+        };
+        if on_one_way && keyboard_input.pressed(KeyCode::Down) && keyboard_input.just_pressed(KeyCode::Space){
+            commands.entity(entity).insert(DropThrough{
+                timer: Timer::from_seconds(DROP_THROUGH_TIME, false),
+            });
+        }else if keyboard_input.just_pressed(KeyCode::Space){
             if player.action != PlayerAction::Jump{
                 player.action = PlayerAction::Jump;
             }
@@ -298,4 +398,3 @@ fn input_player(
     }
 }
 
-
@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+use bevy::sprite::collide_aabb::collide;
+
+use crate::{Direction, Player, PlayerAction, StrikeBox, Platform,
+    Velocity, Element, Damage, DamageKind, WinSize, ApplyStatus, StatusKind, WorldScroll,
+    LevelComplete, RestartRun, Knockback, SIMULATION, TIMESTEP};
+use crate::registry::AssetRegistry;
+
+const PROJECTILE_GRAVITY: f32 = 400.0; //lobbed shots fall slower than bodies
+const PROJECTILE_SIZE: f32 = 14.0;
+
+struct Projectile{
+    element: Element,
+    damage: i32,
+    gravity: bool, //true if the shot arcs down
+    lifetime: Timer,
+}
+
+pub struct ProjectilesPlugin;
+impl Plugin for ProjectilesPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_system_to_stage(SIMULATION, move_projectiles.system().after("update_scroll"))
+            .add_system_to_stage(SIMULATION, resolve_projectiles.system().label("deal_damage"))
+            .add_system(clear_projectiles.system());
+    }
+}
+
+//called by anything that shoots. dir is -1.0 for left, 1.0 for right.
+pub(crate) fn spawn_projectile(
+    commands: &mut Commands,
+    registry: &AssetRegistry,
+    element: Element,
+    position: Vec3,
+    dir: f32,
+    ){
+    //fire flies fast and straight. water is slower and lobbed.
+    let (material, velocity, damage, gravity, lifetime) = match element{
+        Element::Fire => (
+            registry.material("fire_bolt"), Vec3::new(350.0 * dir, 0.0, 0.0), 10, false, 2.0),
+        Element::Water => (
+            registry.material("water_bolt"), Vec3::new(250.0 * dir, 220.0, 0.0), 6, true, 3.0),
+    };
+
+    commands
+        .spawn_bundle(SpriteBundle{
+            material,
+            sprite: Sprite::new(Vec2::new(PROJECTILE_SIZE, PROJECTILE_SIZE)),
+            transform: Transform{
+                translation: position,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Projectile{
+            element,
+            damage,
+            gravity,
+            lifetime: Timer::from_seconds(lifetime, false),
+        })
+        .insert(Velocity{
+            velocity,
+        });
+}
+
+fn move_projectiles(
+    scroll: Res<WorldScroll>,
+    mut projectile_query: Query<(&Projectile, &mut Transform, &mut Velocity)>,
+    ){
+    //projectiles are part of the world so they scroll with the player
+    //the same way enemies do.
+    let delta_seconds = TIMESTEP;
+
+    for(projectile, mut transform, mut projectile_velocity) in projectile_query.iter_mut(){
+        if projectile.gravity{
+            projectile_velocity.velocity.y -= PROJECTILE_GRAVITY * delta_seconds;
+        }
+        let multiplier = transform.translation.z;
+        transform.translation.x += scroll.speed * multiplier * delta_seconds;
+        transform.translation += projectile_velocity.velocity * delta_seconds;
+    }
+}
+
+//hitting the player, hitting a platform and running out all end a
+//shot. one pass so each shot is only despawned once.
+fn resolve_projectiles(
+    mut commands: Commands,
+    window: Res<WinSize>,
+    mut damage_events: EventWriter<Damage>,
+    mut status_events: EventWriter<ApplyStatus>,
+    mut knockback_events: EventWriter<Knockback>,
+    mut projectile_query: Query<(Entity, &mut Projectile, &Transform, &Velocity)>,
+    player_query: Query<(Entity, &Player, &Transform, &StrikeBox)>,
+    platform_query: Query<(&Transform, &Sprite), With<Platform>>,
+    ){
+    let target = player_query.single().ok();
+    let projectile_size = Vec2::new(PROJECTILE_SIZE, PROJECTILE_SIZE);
+
+    for(entity, mut projectile, projectile_tf, projectile_velocity) in projectile_query.iter_mut(){
+        if let Some((player_entity, player, player_tf, strike_box)) = target{
+            let collision = collide(
+                projectile_tf.translation,
+                projectile_size,
+                player_tf.translation,
+                Vec2::new(strike_box.h, strike_box.w),
+               );
+
+            if let Some(_) = collision{
+                //block only works when facing the shot. shots moving left
+                //came from the right.
+                let facing_shot = match player.direction{
+                    Direction::Right => projectile_velocity.velocity.x < 0.0,
+                    Direction::Left => projectile_velocity.velocity.x > 0.0,
+                    _ => false,
+                };
+                if !(player.action == PlayerAction::Block && facing_shot){
+                    damage_events.send(Damage{
+                        target: player_entity,
+                        amount: projectile.damage,
+                        kind: DamageKind::Normal,
+                    });
+                    knockback_events.send(Knockback{
+                        target: player_entity,
+                        from_x: projectile_tf.translation.x,
+                        force: 200.0,
+                        stun: 0.25,
+                    });
+                    //fire burns, water freezes
+                    let (kind, duration) = match projectile.element{
+                        Element::Fire => (StatusKind::Burn, 3.0),
+                        Element::Water => (StatusKind::Freeze, 2.0),
+                    };
+                    status_events.send(ApplyStatus{
+                        target: player_entity,
+                        kind,
+                        duration,
+                    });
+                }
+                commands.entity(entity).despawn();
+                continue;
+            }
+        }
+
+        let hit_platform = platform_query.iter().any(|(platform_tf, platform_sprite)|
+            collide(
+                projectile_tf.translation,
+                projectile_size,
+                platform_tf.translation,
+                platform_sprite.size,
+               ).is_some());
+
+        projectile.lifetime.tick(std::time::Duration::from_secs_f32(TIMESTEP));
+        //also clean up anything that fell off the bottom of the screen
+        let expired = projectile.lifetime.finished() || projectile_tf.translation.y < -window.h;
+
+        if hit_platform || expired{
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn clear_projectiles(
+    mut commands: Commands,
+    mut level_events: EventReader<LevelComplete>,
+    mut restart_events: EventReader<RestartRun>,
+    query: Query<Entity, With<Projectile>>,
+    ){
+    //read both so neither queue is left for next frame
+    let cleared = level_events.iter().count() + restart_events.iter().count();
+    if cleared > 0{
+        for entity in query.iter(){
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use bevy::prelude::*;
+use bevy::asset::{Asset, FileAssetIo};
+use bevy::utils::HashMap;
+use serde::Deserialize;
+
+use crate::loading::LoadingAssets;
+
+const MANIFEST: &str = "assets/manifest.ron";
+
+//assets/manifest.ron, every asset the game uses by id. a path is
+//under assets like anywhere else.
+#[derive(Deserialize)]
+struct Manifest{
+    textures: HashMap<String, String>,
+    colors: HashMap<String, (f32, f32, f32, f32)>, //plain color boxes, rgba
+    atlases: HashMap<String, AtlasSpec>,
+    sounds: HashMap<String, String>,
+    #[serde(default)]
+    music: HashMap<String, String>, //not waited on, a track can be missing
+    fonts: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct AtlasSpec{
+    texture: String,
+    tile_size: (f32, f32),
+    columns: usize,
+    rows: usize,
+}
+
+//what the manifest loads into. spawners ask for things by id. textures
+//and colors are both materials.
+pub(crate) struct AssetRegistry{
+    materials: HashMap<String, Handle<ColorMaterial>>,
+    atlases: HashMap<String, Handle<TextureAtlas>>,
+    sounds: HashMap<String, Handle<AudioSource>>,
+    music: HashMap<String, Handle<AudioSource>>,
+    fonts: HashMap<String, Handle<Font>>,
+}
+
+//an id that isn't in the manifest draws as a blank, it doesn't crash.
+fn lookup<T: Asset>(kind: &str, handles: &HashMap<String, Handle<T>>, id: &str) -> Handle<T>{
+    match handles.get(id){
+        Some(handle) => handle.clone(),
+        None => {
+            warn!("no {} called {} in {}", kind, id, MANIFEST);
+            Handle::default()
+        }
+    }
+}
+
+impl AssetRegistry{
+    pub(crate) fn material(&self, id: &str) -> Handle<ColorMaterial>{
+        lookup("texture or color", &self.materials, id)
+    }
+
+    pub(crate) fn atlas(&self, id: &str) -> Handle<TextureAtlas>{
+        lookup("atlas", &self.atlases, id)
+    }
+
+    pub(crate) fn sound(&self, id: &str) -> Handle<AudioSource>{
+        lookup("sound", &self.sounds, id)
+    }
+
+    pub(crate) fn music(&self, id: &str) -> Handle<AudioSource>{
+        lookup("music track", &self.music, id)
+    }
+
+    pub(crate) fn font(&self, id: &str) -> Handle<Font>{
+        lookup("font", &self.fonts, id)
+    }
+}
+
+pub struct RegistryPlugin;
+impl Plugin for RegistryPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            //before Startup so every spawner can use it
+            .add_startup_system_to_stage(StartupStage::PreStartup, load_registry.system())
+            .add_system(log_reloads::<Texture>.system())
+            .add_system(log_reloads::<AudioSource>.system());
+    }
+}
+
+//read straight off the disk. nothing can be drawn without it, not
+//even the loading screen, so a bad manifest stops the game here.
+//it isn't watched, the files it points to are.
+fn load_registry(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut loading: ResMut<LoadingAssets>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    ){
+    //in a dev build, files changed under assets are loaded again and
+    //swapped in. has to start before anything loads to see it.
+    if cfg!(debug_assertions){
+        if let Err(err) = asset_server.watch_for_changes(){
+            warn!("not watching assets for changes: {}", err);
+        }
+    }
+
+    let path = FileAssetIo::get_root_path().join(MANIFEST);
+    let manifest: Manifest = match std::fs::read_to_string(&path){
+        Ok(text) => match ron::de::from_str(&text){
+            Ok(manifest) => manifest,
+            Err(err) => panic!("{} is broken: {}", path.display(), err),
+        },
+        Err(err) => panic!("could not read {}: {}", path.display(), err),
+    };
+
+    let mut materials = HashMap::default();
+    for(id, texture) in manifest.textures.iter(){
+        let texture: Handle<Texture> = loading.load(&asset_server, texture);
+        materials.insert(id.clone(), color_materials.add(texture.into()));
+    }
+    for(id, (r, g, b, a)) in manifest.colors.iter(){
+        materials.insert(id.clone(), color_materials.add(Color::rgba(*r, *g, *b, *a).into()));
+    }
+
+    let mut atlases = HashMap::default();
+    for(id, spec) in manifest.atlases.iter(){
+        let texture = loading.load(&asset_server, &spec.texture);
+        atlases.insert(id.clone(), texture_atlases.add(
+            TextureAtlas::from_grid(texture, Vec2::new(spec.tile_size.0, spec.tile_size.1),
+                spec.columns, spec.rows)));
+    }
+
+    let mut sounds = HashMap::default();
+    for(id, sound) in manifest.sounds.iter(){
+        sounds.insert(id.clone(), loading.load(&asset_server, sound));
+    }
+
+    let mut music = HashMap::default();
+    for(id, track) in manifest.music.iter(){
+        music.insert(id.clone(), asset_server.load(track.as_str()));
+    }
+
+    let mut fonts = HashMap::default();
+    for(id, font) in manifest.fonts.iter(){
+        fonts.insert(id.clone(), loading.load(&asset_server, font));
+    }
+
+    commands.insert_resource(AssetRegistry{
+        materials,
+        atlases,
+        sounds,
+        music,
+        fonts,
+    });
+}
+
+//sprite sheets and sounds need nothing more than the swap, every
+//handle to them already shows the new file.
+fn log_reloads<T: Asset>(
+    asset_server: Res<AssetServer>,
+    mut asset_events: EventReader<AssetEvent<T>>,
+    ){
+    for event in asset_events.iter(){
+        if let AssetEvent::Modified{handle} = event{
+            //font and other made up textures have no file
+            if let Some(path) = asset_server.get_handle_path(handle.id){
+                info!("reloaded {}", path.path().display());
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+use crate::{Player, Damage, AppState, RestartRun};
+
+const COMBO_WINDOW: f32 = 2.0; //seconds to land the next hit and keep the combo
+
+//sent whenever one of the player's attacks lands.
+pub(crate) struct PlayerHit{
+    pub(crate) points: u32,
+}
+
+//each hit is worth its points times how many hits into the combo it is.
+pub(crate) struct Score{
+    pub(crate) points: u32,
+    pub(crate) combo: u32,
+    combo_timer: Timer,
+}
+
+pub struct ScorePlugin;
+impl Plugin for ScorePlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_event::<PlayerHit>()
+            .insert_resource(Score{
+                points: 0,
+                combo: 0,
+                combo_timer: Timer::from_seconds(COMBO_WINDOW, false),
+            })
+            .add_system(score_hits.system().label("score_hits"))
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(drop_combo.system().after("score_hits"))
+                       )
+            .add_system(restart_score.system());
+    }
+}
+
+fn score_hits(
+    mut score: ResMut<Score>,
+    mut hit_events: EventReader<PlayerHit>,
+    ){
+    for hit in hit_events.iter(){
+        score.combo += 1;
+        score.points += hit.points * score.combo;
+        score.combo_timer.reset();
+    }
+}
+
+//the combo ends when the window runs out or the player gets hit.
+fn drop_combo(
+    time: Res<Time>,
+    mut score: ResMut<Score>,
+    mut damage_events: EventReader<Damage>,
+    player_query: Query<Entity, With<Player>>,
+    ){
+    score.combo_timer.tick(time.delta());
+    let hurt = match player_query.single(){
+        Ok(player) => damage_events.iter().any(|damage| damage.target == player),
+        Err(_) => false,
+    };
+    if score.combo_timer.finished() || hurt{
+        score.combo = 0;
+    }
+}
+
+fn restart_score(
+    mut score: ResMut<Score>,
+    mut restart_events: EventReader<RestartRun>,
+    ){
+    for _ in restart_events.iter(){
+        score.points = 0;
+        score.combo = 0;
+        score.combo_timer.reset();
+    }
+}
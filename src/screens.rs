@@ -0,0 +1,306 @@
+use bevy::prelude::*;
+use bevy::app::AppExit;
+
+use crate::{Player, Energy, AppState, RestartRun, LevelComplete};
+use crate::registry::AssetRegistry;
+use crate::display::PlayArea;
+use crate::score::Score;
+use crate::sound::{VolumeBuses, BUSES};
+
+const HEADING_SIZE: f32 = 60.0;
+const LINE_SIZE: f32 = 24.0;
+const VOLUME_STEP: f32 = 0.1;
+
+//everything on the screen that is showing, cleared when it is left.
+//the game stays drawn underneath.
+pub(crate) struct ScreenUi;
+
+//which volume the arrow keys turn on the pause screen.
+struct PauseMenu{
+    bus: usize, //into BUSES
+}
+
+pub struct ScreensPlugin;
+impl Plugin for ScreensPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .insert_resource(PauseMenu{
+                bus: 0,
+            })
+            .add_system_set(
+                SystemSet::on_enter(AppState::Title)
+                    .with_system(title_spawn.system())
+                       )
+            .add_system_set(
+                SystemSet::on_update(AppState::Title)
+                    .with_system(title_input.system())
+                       )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Title)
+                    .with_system(clear_screen.system())
+                       )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(pause_input.system())
+                    .with_system(player_down.system())
+                    .with_system(level_won.system())
+                       )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Paused)
+                    .with_system(paused_spawn.system())
+                       )
+            .add_system_set(
+                SystemSet::on_update(AppState::Paused)
+                    .with_system(paused_input.system())
+                       )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Paused)
+                    .with_system(clear_screen.system())
+                       )
+            .add_system_set(
+                SystemSet::on_enter(AppState::GameOver)
+                    .with_system(game_over_spawn.system())
+                       )
+            .add_system_set(
+                SystemSet::on_update(AppState::GameOver)
+                    .with_system(game_over_input.system())
+                       )
+            .add_system_set(
+                SystemSet::on_exit(AppState::GameOver)
+                    .with_system(clear_screen.system())
+                       )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Victory)
+                    .with_system(victory_spawn.system())
+                       )
+            .add_system_set(
+                SystemSet::on_update(AppState::Victory)
+                    .with_system(game_over_input.system())
+                       )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Victory)
+                    .with_system(clear_screen.system())
+                       );
+    }
+}
+
+//a screen over the play area with lines of text down the middle. the
+//first line is the heading. more can be added to what comes back.
+pub(crate) fn spawn_screen(
+    commands: &mut Commands,
+    registry: &AssetRegistry,
+    backdrop: Handle<ColorMaterial>,
+    lines: &[String],
+    ) -> Entity{
+    commands
+        .spawn_bundle(NodeBundle{
+            style: Style{
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: backdrop,
+            ..Default::default()
+        })
+        .insert(ScreenUi)
+        .insert(PlayArea)
+        .with_children(|screen|{
+            for(index, line) in lines.iter().enumerate(){
+                screen.spawn_bundle(TextBundle{
+                    style: Style{
+                        margin: Rect::all(Val::Px(8.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        line.as_str(),
+                        TextStyle{
+                            font: registry.font("hud"),
+                            font_size: if index == 0{ HEADING_SIZE }else{ LINE_SIZE },
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                        ),
+                    ..Default::default()
+                });
+            }
+        })
+        .id()
+}
+
+pub(crate) fn clear_screen(
+    mut commands: Commands,
+    query: Query<Entity, With<ScreenUi>>,
+    ){
+    for entity in query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn title_spawn(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    ){
+    spawn_screen(&mut commands, &registry, registry.material("screen_dim"), &[
+        "Night Ward".to_string(),
+        "Enter to start".to_string(),
+    ]);
+}
+
+fn title_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    ){
+    if keyboard_input.just_pressed(KeyCode::Return){
+        //the state changes within this frame, don't let the
+        //next screen see the same press
+        keyboard_input.reset(KeyCode::Return);
+        state.set(AppState::Playing).unwrap();
+    }
+}
+
+//Paused goes on top of Playing so nothing in the level is torn down.
+fn pause_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    ){
+    for key in [KeyCode::Escape, KeyCode::P].iter(){
+        if keyboard_input.just_pressed(*key){
+            keyboard_input.reset(*key);
+            state.push(AppState::Paused).ok();
+            return;
+        }
+    }
+}
+
+//only on a change, so the old player still lying there for a
+//frame after a retry doesn't end the new run.
+fn player_down(
+    mut state: ResMut<State<AppState>>,
+    query: Query<&Energy, (With<Player>, Changed<Energy>)>,
+    ){
+    if let Ok(energy) = query.single(){
+        if energy.power <= 0{
+            state.set(AppState::GameOver).ok();
+        }
+    }
+}
+
+//the boss falling, the exit or dawn. there is only the one level,
+//so winning it ends the run.
+fn level_won(
+    mut state: ResMut<State<AppState>>,
+    mut level_events: EventReader<LevelComplete>,
+    ){
+    if level_events.iter().count() > 0{
+        state.set(AppState::Victory).ok();
+    }
+}
+
+fn paused_spawn(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    buses: Res<VolumeBuses>,
+    menu: Res<PauseMenu>,
+    ){
+    spawn_paused(&mut commands, &registry, &buses, &menu);
+}
+
+fn spawn_paused(
+    commands: &mut Commands,
+    registry: &AssetRegistry,
+    buses: &VolumeBuses,
+    menu: &PauseMenu,
+    ){
+    let mut lines = vec![
+        "Paused".to_string(),
+        "Esc to carry on".to_string(),
+    ];
+    for(index, bus) in BUSES.iter().enumerate(){
+        let pointer = if index == menu.bus{ "> " }else{ "" };
+        lines.push(format!("{}{} {}%", pointer, bus.name(), (buses.get(*bus) * 100.0).round()));
+    }
+    lines.push("Up and Down to pick, Left and Right to turn".to_string());
+    spawn_screen(commands, registry, registry.material("screen_dim"), &lines);
+}
+
+fn paused_input(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    mut buses: ResMut<VolumeBuses>,
+    mut menu: ResMut<PauseMenu>,
+    screen_query: Query<Entity, With<ScreenUi>>,
+    ){
+    for key in [KeyCode::Escape, KeyCode::P].iter(){
+        if keyboard_input.just_pressed(*key){
+            keyboard_input.reset(*key);
+            state.pop().unwrap();
+            return;
+        }
+    }
+
+    let bus = BUSES[menu.bus];
+    if keyboard_input.just_pressed(KeyCode::Up){
+        menu.bus = (menu.bus + BUSES.len() - 1) % BUSES.len();
+    }else if keyboard_input.just_pressed(KeyCode::Down){
+        menu.bus = (menu.bus + 1) % BUSES.len();
+    }else if keyboard_input.just_pressed(KeyCode::Left){
+        buses.nudge(bus, -VOLUME_STEP);
+    }else if keyboard_input.just_pressed(KeyCode::Right){
+        buses.nudge(bus, VOLUME_STEP);
+    }else{
+        return;
+    }
+
+    //the lines are only text, put the screen up again with the new ones
+    for entity in screen_query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_paused(&mut commands, &registry, &buses, &menu);
+}
+
+fn game_over_spawn(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    score: Res<Score>,
+    ){
+    spawn_screen(&mut commands, &registry, registry.material("screen_dim"), &[
+        "Game Over".to_string(),
+        format!("Score {}", score.points),
+        "Enter to try again".to_string(),
+        "Esc to quit".to_string(),
+    ]);
+}
+
+fn victory_spawn(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    score: Res<Score>,
+    ){
+    spawn_screen(&mut commands, &registry, registry.material("screen_dim"), &[
+        "Level Complete".to_string(),
+        format!("Score {}", score.points),
+        "Enter to play again".to_string(),
+        "Esc to quit".to_string(),
+    ]);
+}
+
+//also ends the level won screen, the choices are the same.
+fn game_over_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    mut restart_events: EventWriter<RestartRun>,
+    mut exit_events: EventWriter<AppExit>,
+    ){
+    if keyboard_input.just_pressed(KeyCode::Return){
+        keyboard_input.reset(KeyCode::Return);
+        restart_events.send(RestartRun);
+        state.set(AppState::Playing).unwrap();
+    }else if keyboard_input.just_pressed(KeyCode::Escape){
+        exit_events.send(AppExit);
+    }
+}
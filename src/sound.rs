@@ -0,0 +1,280 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use rand::prelude::*;
+use rodio::{OutputStream, OutputStreamHandle, Sink, Source};
+use rodio::source::ChannelVolume;
+
+use crate::{Player, PlayerAction, Gravity, WinSize, AppState};
+use crate::registry::AssetRegistry;
+
+const HEARING_RANGE: f32 = 600.0; //past the screen edge, enemies fade out over this far
+
+//every sound the game makes. gameplay sends a PlayCue and this
+//file works out which file, how loud and how often.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Cue{
+    Jump,
+    Land,
+    Charge,
+    Footstep,
+    WindUp,
+    Cast,
+}
+
+const CUES: [Cue; 6] = [Cue::Jump, Cue::Land, Cue::Charge, Cue::Footstep, Cue::WindUp, Cue::Cast];
+
+pub(crate) struct PlayCue{
+    pub(crate) cue: Cue,
+    pub(crate) from_x: Option<f32>, //where it came from. None is the player, dead center
+}
+
+struct CueSettings{
+    sound: &'static str, //id in the manifest
+    volume: f32,
+    pitch_jitter: f32, //pitch lands somewhere in 1.0 +- this
+    cooldown: f32, //seconds before the same cue can play again
+}
+
+fn cue_settings(cue: Cue) -> CueSettings{
+    match cue{
+        Cue::Jump => CueSettings{
+            sound: "jump",
+            volume: 0.6,
+            pitch_jitter: 0.08,
+            cooldown: 0.1,
+        },
+        Cue::Land => CueSettings{
+            sound: "land",
+            volume: 0.7,
+            pitch_jitter: 0.1,
+            cooldown: 0.15,
+        },
+        Cue::Charge => CueSettings{
+            sound: "thrust",
+            volume: 0.8,
+            pitch_jitter: 0.05,
+            cooldown: 0.3,
+        },
+        Cue::Footstep => CueSettings{
+            sound: "leaves",
+            volume: 0.35,
+            pitch_jitter: 0.15,
+            cooldown: 0.35, //also the walking pace
+        },
+        Cue::WindUp => CueSettings{
+            sound: "wind_up",
+            volume: 0.8,
+            pitch_jitter: 0.1,
+            cooldown: 0.2,
+        },
+        Cue::Cast => CueSettings{
+            sound: "burst_fire",
+            volume: 0.7,
+            pitch_jitter: 0.1,
+            cooldown: 0.2,
+        },
+    }
+}
+
+//our own output so each sound gets a sink we can set up. bevy's
+//Audio can only play a sound as it is. None if there is no device.
+pub(crate) struct SoundOutput{
+    pub(crate) stream: Option<(OutputStream, OutputStreamHandle)>,
+}
+
+//volume knobs, turned on the pause screen. everything is scaled by
+//master, then by its own bus.
+pub(crate) struct VolumeBuses{
+    pub(crate) master: f32,
+    pub(crate) music: f32,
+    pub(crate) sfx: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Bus{
+    Master,
+    Music,
+    Sfx,
+}
+
+pub(crate) const BUSES: [Bus; 3] = [Bus::Master, Bus::Music, Bus::Sfx];
+
+impl Bus{
+    pub(crate) fn name(&self) -> &'static str{
+        match self{
+            Bus::Master => "Volume",
+            Bus::Music => "Music",
+            Bus::Sfx => "Sound",
+        }
+    }
+}
+
+impl VolumeBuses{
+    pub(crate) fn get(&self, bus: Bus) -> f32{
+        match bus{
+            Bus::Master => self.master,
+            Bus::Music => self.music,
+            Bus::Sfx => self.sfx,
+        }
+    }
+
+    //kept between silent and full
+    pub(crate) fn nudge(&mut self, bus: Bus, by: f32){
+        let level = match bus{
+            Bus::Master => &mut self.master,
+            Bus::Music => &mut self.music,
+            Bus::Sfx => &mut self.sfx,
+        };
+        *level = (*level + by).max(0.0).min(1.0);
+    }
+}
+
+struct CueSounds{
+    handles: HashMap<Cue, Handle<AudioSource>>,
+    last_played: HashMap<Cue, f64>,
+}
+
+pub struct SoundPlugin;
+impl Plugin for SoundPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_event::<PlayCue>()
+            .insert_non_send_resource(SoundOutput{
+                stream: OutputStream::try_default().ok(),
+            })
+            .insert_resource(VolumeBuses{
+                master: 1.0,
+                music: 0.7,
+                sfx: 1.0,
+            })
+            .add_startup_system(load_cues.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(footsteps.system())
+                       )
+            .add_system_to_stage(CoreStage::PostUpdate, play_cues.system());
+    }
+}
+
+fn load_cues(
+    mut commands: Commands,
+    registry: Res<AssetRegistry>,
+    ){
+    let mut handles = HashMap::default();
+    for cue in CUES.iter(){
+        handles.insert(*cue, registry.sound(cue_settings(*cue).sound));
+    }
+    commands.insert_resource(CueSounds{
+        handles,
+        last_played: HashMap::default(),
+    });
+}
+
+fn footsteps(
+    mut cue_events: EventWriter<PlayCue>,
+    query: Query<(&Player, &Gravity)>,
+    ){
+    if let Ok((player, gravity)) = query.single(){
+        if player.action == PlayerAction::Walk && !gravity.falling{
+            //the cooldown spaces the steps out
+            cue_events.send(PlayCue{
+                cue: Cue::Footstep,
+                from_x: None,
+            });
+        }
+    }
+}
+
+//left and right volume for a sound at x. pans across the screen and
+//gets quieter the farther it is from the player.
+fn stereo(from_x: f32, player_x: f32, window: &WinSize) -> (f32, f32){
+    let offset = from_x - player_x;
+    let half = window.w / 2.0;
+    let pan = (offset / half).max(-1.0).min(1.0);
+    let gain = (1.0 - offset.abs() / (half + HEARING_RANGE)).max(0.0);
+    //equal power, so a sound in the middle isn't louder than at the sides
+    let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+    (angle.cos() * gain, angle.sin() * gain)
+}
+
+fn play_cues(
+    output: NonSend<SoundOutput>,
+    buses: Res<VolumeBuses>,
+    window: Res<WinSize>,
+    player_query: Query<&Player>,
+    time: Res<Time>,
+    sources: Res<Assets<AudioSource>>,
+    mut sounds: ResMut<CueSounds>,
+    mut cue_events: EventReader<PlayCue>,
+    ){
+    let now = time.seconds_since_startup();
+    let mut rng = thread_rng();
+
+    for event in cue_events.iter(){
+        let settings = cue_settings(event.cue);
+        if let Some(last) = sounds.last_played.get(&event.cue){
+            if now - last < settings.cooldown as f64{
+                continue;
+            }
+        }
+        let (_, stream_handle) = match &output.stream{
+            Some(stream) => stream,
+            None => continue,
+        };
+        //still loading, skip it rather than play it late
+        let source = match sounds.handles.get(&event.cue).and_then(|handle| sources.get(handle)){
+            Some(source) => source,
+            None => continue,
+        };
+        let sink = match Sink::try_new(stream_handle){
+            Ok(sink) => sink,
+            Err(_) => continue,
+        };
+        let pitch = 1.0 + rng.gen_range(-settings.pitch_jitter..=settings.pitch_jitter);
+        sink.set_volume(settings.volume * buses.sfx * buses.master);
+        match (event.from_x, player_query.single()){
+            (Some(x), Ok(player)) => {
+                let (left, right) = stereo(x, player.current_x, &window);
+                if left + right <= 0.0{
+                    continue;
+                }
+                sink.append(ChannelVolume::new(source.decoder().speed(pitch), vec![left, right]));
+            }
+            _ => sink.append(source.decoder().speed(pitch)),
+        }
+        sink.detach();
+        sounds.last_played.insert(event.cue, now);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const WINDOW: WinSize = WinSize{ h: 700.0, w: 900.0 };
+
+    #[test]
+    fn middle_is_even(){
+        let (left, right) = stereo(100.0, 100.0, &WINDOW);
+        assert!((left - right).abs() < 1e-6);
+        //equal power, full volume
+        assert!((left * left + right * right - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sides_pan_and_fade(){
+        let (left, right) = stereo(450.0, 0.0, &WINDOW);
+        assert!(left.abs() < 1e-6);
+        assert!((right - (1.0 - 450.0 / (450.0 + HEARING_RANGE))).abs() < 1e-5);
+        //the same on the other side, mirrored
+        let (left, right) = stereo(-450.0, 0.0, &WINDOW);
+        assert!(right.abs() < 1e-6);
+        assert!(left > 0.0);
+    }
+
+    #[test]
+    fn out_of_earshot_is_silent(){
+        let (left, right) = stereo(450.0 + HEARING_RANGE + 1.0, 0.0, &WINDOW);
+        assert_eq!((left, right), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+
+use crate::{Player, SIMULATION, TIMESTEP};
+
+pub(crate) const CHARGE_STAMINA: f32 = 30.0;
+const STAMINA_REGEN: f32 = 15.0; //per second
+
+//spent on charges, comes back on its own.
+pub(crate) struct Stamina{
+    pub(crate) current: f32,
+    pub(crate) max: f32,
+}
+
+impl Stamina{
+    pub(crate) fn full(max: f32) -> Self{
+        Stamina{
+            current: max,
+            max,
+        }
+    }
+
+    //false, and nothing is taken, if there isn't enough
+    pub(crate) fn spend(&mut self, amount: f32) -> bool{
+        if self.current < amount{
+            return false;
+        }
+        self.current -= amount;
+        true
+    }
+}
+
+pub struct StaminaPlugin;
+impl Plugin for StaminaPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_system_to_stage(SIMULATION, recover_stamina.system());
+    }
+}
+
+fn recover_stamina(
+    mut query: Query<&mut Stamina, With<Player>>,
+    ){
+    for mut stamina in query.iter_mut(){
+        stamina.current = (stamina.current + STAMINA_REGEN * TIMESTEP).min(stamina.max);
+    }
+}
@@ -0,0 +1,160 @@
+use bevy::prelude::*;
+
+use crate::{Player, Enemy, PlayerAction, Gravity, Velocity, Damage, DamageKind,
+    ApplyStatus, StatusKind, AppState, SPEEDSTOP, ENEMYSPEEDSTOP, SIMULATION, TIMESTEP};
+
+const BURN_DAMAGE: i32 = 2; //per stack, every BURN_TICK seconds
+const BURN_TICK: f32 = 0.5;
+const MAX_BURN_STACKS: u32 = 3;
+const FREEZE_SLOW: f32 = 0.4; //frozen bodies move at 40% speed
+
+struct StatusEffect{
+    kind: StatusKind,
+    stacks: u32,
+    timer: Timer, //how long the effect lasts
+    tick: Timer, //for effects that work over time
+}
+
+//every body that can be affected carries one of these.
+//send an ApplyStatus event to add to it.
+pub(crate) struct StatusEffects{
+    effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects{
+    pub(crate) fn new() -> Self{
+        StatusEffects{
+            effects: Vec::new(),
+        }
+    }
+
+    pub(crate) fn has(&self, kind: StatusKind) -> bool{
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    //stun and knockdown take away control
+    pub(crate) fn locks_input(&self) -> bool{
+        self.has(StatusKind::Stun) || self.has(StatusKind::Knockdown)
+    }
+
+    pub(crate) fn speed_factor(&self) -> f32{
+        if self.has(StatusKind::Freeze){
+            FREEZE_SLOW
+        }else{
+            1.0
+        }
+    }
+}
+
+//use anywhere vel_mod is turned into movement.
+pub(crate) fn slowed(vel_mod: f32, status: Option<&StatusEffects>) -> f32{
+    match status{
+        Some(status) => vel_mod * status.speed_factor(),
+        None => vel_mod,
+    }
+}
+
+pub struct StatusPlugin;
+impl Plugin for StatusPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(apply_status.system())
+                    .with_system(tint_status.system().label("status_tint"))
+                       )
+            .add_system_to_stage(SIMULATION, tick_status.system().label("deal_damage"));
+    }
+}
+
+fn apply_status(
+    mut status_events: EventReader<ApplyStatus>,
+    mut query: Query<(&mut StatusEffects, &mut Transform, &mut Gravity, &mut Velocity,
+                      Option<&mut Player>, Option<&mut Enemy>)>,
+    ){
+    for event in status_events.iter(){
+        if let Ok((mut status, mut transform, mut gravity, mut velocity, player, enemy)) = 
+            query.get_mut(event.target){
+            let duration = event.duration;
+
+            //burn stacks up and refreshes. everything else just refreshes.
+            if let Some(effect) = status.effects.iter_mut().find(|e| e.kind == event.kind){
+                if event.kind == StatusKind::Burn && effect.stacks < MAX_BURN_STACKS{
+                    effect.stacks += 1;
+                }
+                if duration > effect.timer.duration().as_secs_f32() - effect.timer.elapsed_secs(){
+                    effect.timer = Timer::from_seconds(duration, false);
+                }
+            }else{
+                status.effects.push(StatusEffect{
+                    kind: event.kind,
+                    stacks: 1,
+                    timer: Timer::from_seconds(duration, false),
+                    tick: Timer::from_seconds(BURN_TICK, true),
+                });
+            }
+
+            //stun and knockdown stop whatever the body was doing.
+            match event.kind{
+                StatusKind::Stun | StatusKind::Knockdown => {
+                    if let Some(mut player) = player{
+                        player.vel_mod = SPEEDSTOP;
+                        player.action = PlayerAction::Stand;
+                    }
+                    if let Some(mut enemy) = enemy{
+                        enemy.vel_mod = ENEMYSPEEDSTOP;
+                        enemy.action = PlayerAction::Stand;
+                    }
+                    if event.kind == StatusKind::Knockdown{
+                        //knock the body off its feet
+                        transform.translation.y += 10.0; //get it off platform
+                        velocity.velocity.y = 150.0;
+                        gravity.falling = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn tick_status(
+    mut damage_events: EventWriter<Damage>,
+    mut query: Query<(Entity, &mut StatusEffects)>,
+    ){
+    for(entity, mut status) in query.iter_mut(){
+        for effect in status.effects.iter_mut(){
+            effect.timer.tick(std::time::Duration::from_secs_f32(TIMESTEP));
+            if effect.kind == StatusKind::Burn{
+                effect.tick.tick(std::time::Duration::from_secs_f32(TIMESTEP));
+                if effect.tick.just_finished(){
+                    damage_events.send(Damage{
+                        target: entity,
+                        amount: BURN_DAMAGE * effect.stacks as i32,
+                        kind: DamageKind::Status,
+                    });
+                }
+            }
+        }
+        status.effects.retain(|effect| !effect.timer.finished());
+    }
+}
+
+fn tint_status(
+    mut query: Query<(&StatusEffects, &mut TextureAtlasSprite)>,
+    ){
+    for(status, mut sprite) in query.iter_mut(){
+        //the most severe effect wins the tint
+        sprite.color = if status.has(StatusKind::Knockdown){
+            Color::rgb(0.6, 0.6, 0.6)
+        }else if status.has(StatusKind::Stun){
+            Color::rgb(1.0, 1.0, 0.5)
+        }else if status.has(StatusKind::Freeze){
+            Color::rgb(0.5, 0.75, 1.0)
+        }else if status.has(StatusKind::Burn){
+            Color::rgb(1.0, 0.55, 0.35)
+        }else{
+            Color::WHITE
+        };
+    }
+}
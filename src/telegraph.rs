@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+
+use crate::{Enemy, PlayerAction, Difficulty, AppState, ENEMYSPEEDSTOP, SIMULATION, TIMESTEP};
+use crate::sound::{PlayCue, Cue};
+
+pub(crate) struct TelegraphSpec{
+    windup: f32, //seconds before the attack goes live
+    tint: Color,
+    sound: Option<Cue>,
+}
+
+//one entry per telegraphed attack. tweak these to change how
+//readable each attack is.
+pub(crate) struct Telegraphs{
+    charge: TelegraphSpec,
+    chop: TelegraphSpec,
+    cast: TelegraphSpec,
+}
+
+impl Default for Telegraphs{
+    fn default() -> Self{
+        Telegraphs{
+            charge: TelegraphSpec{
+                windup: 0.5,
+                tint: Color::rgb(1.0, 0.35, 0.35),
+                sound: Some(Cue::WindUp),
+            },
+            chop: TelegraphSpec{
+                windup: 0.35,
+                tint: Color::rgb(1.0, 0.8, 0.3),
+                sound: None,
+            },
+            cast: TelegraphSpec{
+                windup: 0.6,
+                tint: Color::rgb(0.8, 0.5, 1.0),
+                sound: None,
+            },
+        }
+    }
+}
+
+//an enemy that is winding up. the attack starts when the timer runs out.
+struct Telegraph{
+    next: PlayerAction,
+    tint: Color,
+    sound: Option<Cue>, //played on the first frame
+    timer: Timer,
+}
+
+pub struct TelegraphPlugin;
+impl Plugin for TelegraphPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .init_resource::<Telegraphs>()
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(flash_telegraph.system().after("status_tint"))
+                       )
+            .add_system_to_stage(SIMULATION, run_telegraph.system());
+    }
+}
+
+//use instead of setting enemy.action to an attack directly.
+//is_cast picks the spell wind-up over the melee chop.
+pub(crate) fn start_telegraph(
+    commands: &mut Commands,
+    telegraphs: &Telegraphs,
+    difficulty: &Difficulty,
+    entity: Entity,
+    enemy: &mut Enemy,
+    next: PlayerAction,
+    is_cast: bool,
+    ){
+    let spec = match next{
+        PlayerAction::Charge => &telegraphs.charge,
+        PlayerAction::Chop if is_cast => &telegraphs.cast,
+        PlayerAction::Chop => &telegraphs.chop,
+        _ => {
+            //nothing to warn about
+            enemy.action = next;
+            return;
+        }
+    };
+
+    enemy.action = PlayerAction::WindUp;
+    enemy.vel_mod = ENEMYSPEEDSTOP;
+    commands.entity(entity).insert(Telegraph{
+        next,
+        tint: spec.tint,
+        sound: spec.sound,
+        timer: Timer::from_seconds(spec.windup * difficulty.telegraph_scale(), false),
+    });
+}
+
+fn run_telegraph(
+    mut commands: Commands,
+    mut cue_events: EventWriter<PlayCue>,
+    mut query: Query<(Entity, &mut Telegraph, &mut Enemy, &Transform)>,
+    ){
+    for(entity, mut telegraph, mut enemy, transform) in query.iter_mut(){
+        //hit or stunned during the wind-up, the attack is lost
+        if enemy.action != PlayerAction::WindUp{
+            commands.entity(entity).remove::<Telegraph>();
+            continue;
+        }
+
+        if let Some(cue) = telegraph.sound.take(){
+            cue_events.send(PlayCue{
+                cue,
+                from_x: Some(transform.translation.x),
+            });
+        }
+
+        telegraph.timer.tick(std::time::Duration::from_secs_f32(TIMESTEP));
+        if telegraph.timer.finished(){
+            enemy.action = telegraph.next;
+            commands.entity(entity).remove::<Telegraph>();
+        }
+    }
+}
+
+//every frame, tint_status has just put the color back
+fn flash_telegraph(
+    mut query: Query<(&Telegraph, &Enemy, &mut TextureAtlasSprite)>,
+    ){
+    for(telegraph, enemy, mut sprite) in query.iter_mut(){
+        if enemy.action != PlayerAction::WindUp{
+            continue;
+        }
+        //flash between the warning color and normal
+        if (telegraph.timer.elapsed_secs() * 10.0) as i32 % 2 == 0{
+            sprite.color = telegraph.tint;
+        }
+    }
+}
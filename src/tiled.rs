@@ -0,0 +1,709 @@
+use bevy::prelude::*;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::PlatformKind;
+use crate::level::{Level, ParallaxPreset, EnemyKind, EnemySpawn, PickupKind, PickupSpec,
+    GroundTiles};
+use crate::platforms::PlatformSpec;
+use crate::ground::PitSpec;
+use crate::hazards::{HazardKind, HazardSpec};
+
+//tiled keeps flip flags in the top bits of a tile id
+const TILE_ID_MASK: u32 = 0x1FFF_FFFF;
+const DEFAULT_HEALTH: i32 = 25;
+
+//just the parts of a tiled map we use. a json map (.tmj) is read
+//straight into these, an xml one (.tmx) by read_tmx.
+#[derive(Deserialize)]
+struct TiledMap{
+    height: u32,
+    tilewidth: f32,
+    tileheight: f32,
+    layers: Vec<TiledLayer>,
+    #[serde(default)]
+    tilesets: Vec<TiledTileset>,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum TiledLayer{
+    #[serde(rename = "tilelayer")]
+    Tiles{
+        width: u32,
+        data: Vec<u32>,
+        #[serde(default)]
+        properties: Vec<TiledProperty>,
+    },
+    #[serde(rename = "objectgroup")]
+    Objects{
+        objects: Vec<TiledObject>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct TiledObject{
+    //"type" before tiled 1.9, "class" after
+    #[serde(rename = "type", alias = "class", default)]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Deserialize)]
+struct TiledTileset{
+    firstgid: u32,
+    #[serde(default)]
+    image: Option<String>,
+    #[serde(default)]
+    columns: usize,
+    #[serde(default)]
+    tilecount: usize,
+}
+
+#[derive(Deserialize)]
+struct TiledProperty{
+    name: String,
+    value: Value,
+}
+
+fn property<'a>(properties: &'a [TiledProperty], name: &str) -> Option<&'a Value>{
+    properties.iter().find(|p| p.name == name).map(|p| &p.value)
+}
+
+fn float_property(properties: &[TiledProperty], name: &str, default: f32) -> f32{
+    property(properties, name).and_then(|v| v.as_f64()).map(|v| v as f32).unwrap_or(default)
+}
+
+fn string_property<'a>(properties: &'a [TiledProperty], name: &str) -> Option<&'a str>{
+    property(properties, name).and_then(|v| v.as_str())
+}
+
+#[derive(Default)]
+pub(crate) struct TiledLoader;
+
+impl AssetLoader for TiledLoader{
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+        ) -> BoxedFuture<'a, Result<(), anyhow::Error>>{
+        Box::pin(async move{
+            let map = if load_context.path().extension().map_or(false, |ext| ext == "tmx"){
+                read_tmx(std::str::from_utf8(bytes)?)?
+            }else{
+                serde_json::from_slice(bytes)?
+            };
+            //tileset images are relative to the map file
+            let folder = load_context.path().parent().map(|p| p.to_path_buf()).unwrap_or_default();
+            let level = to_level(&map, |image| folder.join(image).to_string_lossy().into_owned())?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str]{
+        &["tmj", "tmx"]
+    }
+}
+
+//an attribute that has to be there.
+fn attribute<T: std::str::FromStr>(node: roxmltree::Node, name: &str) -> Result<T, anyhow::Error>{
+    attribute_or(node, name, None)
+}
+
+fn attribute_or<T: std::str::FromStr>(node: roxmltree::Node, name: &str, default: Option<T>)
+    -> Result<T, anyhow::Error>{
+    match (node.attribute(name), default){
+        (Some(text), _) => text.parse()
+            .map_err(|_| anyhow::anyhow!("<{}> has a bad {}: {}", node.tag_name().name(), name, text)),
+        (None, Some(default)) => Ok(default),
+        (None, None) => anyhow::bail!("<{}> has no {}", node.tag_name().name(), name),
+    }
+}
+
+fn child<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> Option<roxmltree::Node<'a, 'input>>{
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+//the <properties> under a map, layer or object. the value is typed
+//like it is in json so the same lookups work on both.
+fn xml_properties(node: roxmltree::Node) -> Result<Vec<TiledProperty>, anyhow::Error>{
+    let mut properties = Vec::new();
+    let list = match child(node, "properties"){
+        Some(list) => list,
+        None => return Ok(properties),
+    };
+    for property in list.children().filter(|child| child.has_tag_name("property")){
+        let name: String = attribute(property, "name")?;
+        //a string with line breaks is the text instead of a value
+        let text = property.attribute("value").or_else(|| property.text()).unwrap_or("");
+        let bad = || anyhow::anyhow!("property {} has a bad value: {}", name, text);
+        let value = match property.attribute("type").unwrap_or("string"){
+            "int" => Value::from(text.parse::<i64>().map_err(|_| bad())?),
+            "float" => Value::from(text.parse::<f64>().map_err(|_| bad())?),
+            "bool" => Value::from(text == "true"),
+            _ => Value::from(text),
+        };
+        properties.push(TiledProperty{
+            name,
+            value,
+        });
+    }
+    Ok(properties)
+}
+
+//tile ids as csv, or one <tile> each in older maps.
+fn xml_tiles(layer: roxmltree::Node) -> Result<Vec<u32>, anyhow::Error>{
+    let data = match child(layer, "data"){
+        Some(data) => data,
+        None => anyhow::bail!("a tile layer has no data"),
+    };
+    if child(data, "chunk").is_some(){
+        anyhow::bail!("infinite maps aren't supported, turn off Infinite in the map properties");
+    }
+    match data.attribute("encoding"){
+        Some("csv") => data.text().unwrap_or("").split(',')
+            .map(|gid| gid.trim())
+            .filter(|gid| !gid.is_empty())
+            .map(|gid| gid.parse().map_err(|_| anyhow::anyhow!("bad tile id {}", gid)))
+            .collect(),
+        None => data.children().filter(|child| child.has_tag_name("tile"))
+            .map(|tile| attribute_or(tile, "gid", Some(0)))
+            .collect(),
+        Some(other) => anyhow::bail!("tile data in {} isn't supported, set the map's tile layer format to CSV", other),
+    }
+}
+
+//a .tmx map is the same map as xml. read into the json structs so
+//both go through to_level.
+fn read_tmx(text: &str) -> Result<TiledMap, anyhow::Error>{
+    let doc = roxmltree::Document::parse(text)?;
+    let root = doc.root_element();
+    if !root.has_tag_name("map"){
+        anyhow::bail!("not a tiled map, it starts with <{}>", root.tag_name().name());
+    }
+    let mut map = TiledMap{
+        height: attribute(root, "height")?,
+        tilewidth: attribute(root, "tilewidth")?,
+        tileheight: attribute(root, "tileheight")?,
+        layers: Vec::new(),
+        tilesets: Vec::new(),
+        properties: xml_properties(root)?,
+    };
+
+    for node in root.children().filter(|child| child.is_element()){
+        match node.tag_name().name(){
+            "tileset" => map.tilesets.push(TiledTileset{
+                firstgid: attribute(node, "firstgid")?,
+                //an external tileset has a source and no image
+                image: child(node, "image").and_then(|image| image.attribute("source")).map(String::from),
+                columns: attribute_or(node, "columns", Some(0))?,
+                tilecount: attribute_or(node, "tilecount", Some(0))?,
+            }),
+            "layer" => map.layers.push(TiledLayer::Tiles{
+                width: attribute(node, "width")?,
+                data: xml_tiles(node)?,
+                properties: xml_properties(node)?,
+            }),
+            "objectgroup" => {
+                let mut objects = Vec::new();
+                for object in node.children().filter(|child| child.has_tag_name("object")){
+                    //"type" before tiled 1.9, "class" after
+                    let kind = object.attribute("type").or_else(|| object.attribute("class")).unwrap_or("");
+                    objects.push(TiledObject{
+                        kind: kind.to_string(),
+                        name: object.attribute("name").unwrap_or("").to_string(),
+                        x: attribute_or(object, "x", Some(0.0))?,
+                        y: attribute_or(object, "y", Some(0.0))?,
+                        width: attribute_or(object, "width", Some(0.0))?,
+                        height: attribute_or(object, "height", Some(0.0))?,
+                        properties: xml_properties(object)?,
+                    });
+                }
+                map.layers.push(TiledLayer::Objects{
+                    objects,
+                });
+            }
+            "imagelayer" | "group" => map.layers.push(TiledLayer::Other),
+            _ => {}
+        }
+    }
+    Ok(map)
+}
+
+//turns a tiled map into the same Level the .level.ron files make.
+//tiled counts y down from the top, levels count up from the bottom.
+fn to_level(map: &TiledMap, image_path: impl Fn(&str) -> String) -> Result<Level, anyhow::Error>{
+    let map_h = map.height as f32 * map.tileheight;
+    let objects: Vec<&TiledObject> = map.layers.iter()
+        .filter_map(|layer| match layer{
+            TiledLayer::Objects{objects} => Some(objects),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+
+    //the player starts at x 0.0, everything else is placed from there
+    let origin_x = objects.iter()
+        .find(|object| object.kind.eq_ignore_ascii_case("player"))
+        .map(|object| object.x)
+        .unwrap_or(0.0);
+
+    let parallax = match string_property(&map.properties, "parallax"){
+        None | Some("Forest") => ParallaxPreset::Forest,
+        Some("Distant") => ParallaxPreset::Distant,
+        Some("Still") => ParallaxPreset::Still,
+        Some(other) => anyhow::bail!("unknown parallax preset {}", other),
+    };
+
+    let mut level = Level{
+        parallax,
+        platforms: Vec::new(),
+        ground: Vec::new(),
+        pits: Vec::new(),
+        hazards: Vec::new(),
+        enemies: Vec::new(),
+        pickups: Vec::new(),
+        checkpoints: Vec::new(),
+        exit: None,
+        endless_seed: property(&map.properties, "endless_seed").and_then(|v| v.as_u64()),
+    };
+
+    for object in objects.iter(){
+        let props = &object.properties;
+        match object.kind.to_lowercase().as_str(){
+            "platform" => {
+                level.platforms.push(PlatformSpec{
+                    x: object.x + object.width / 2.0 - origin_x,
+                    y: map_h - (object.y + object.height / 2.0),
+                    w: object.width,
+                    h: object.height,
+                    kind: platform_kind(props)?,
+                    hidden: false,
+                });
+            }
+            "enemy" => {
+                //kind can be a property or just the object's name
+                let kind = string_property(props, "kind").unwrap_or(object.name.as_str());
+                let kind = match kind{
+                    "Skeleton" => EnemyKind::Skeleton,
+                    "FireMage" => EnemyKind::FireMage,
+                    "WaterMage" => EnemyKind::WaterMage,
+                    "Minotaur" => EnemyKind::Minotaur,
+                    other => anyhow::bail!("unknown enemy kind {}", other),
+                };
+                level.enemies.push(EnemySpawn{
+                    kind,
+                    x: object.x - origin_x,
+                    power: property(props, "power").and_then(|v| v.as_i64()).map(|v| v as i32),
+                });
+            }
+            "pickup" => {
+                let health = property(props, "health").and_then(|v| v.as_i64())
+                    .map(|v| v as i32).unwrap_or(DEFAULT_HEALTH);
+                level.pickups.push(PickupSpec{
+                    kind: PickupKind::Health(health),
+                    x: object.x - origin_x,
+                    y: map_h - object.y,
+                });
+            }
+            "pit" => level.pits.push(PitSpec{
+                x: object.x - origin_x,
+                w: object.width,
+                lethal: property(props, "lethal").and_then(|v| v.as_bool()).unwrap_or(false),
+            }),
+            "hazard" => {
+                level.hazards.push(HazardSpec{
+                    x: object.x + object.width / 2.0 - origin_x,
+                    y: map_h - (object.y + object.height / 2.0),
+                    w: object.width,
+                    h: object.height,
+                    kind: hazard_kind(props)?,
+                    lethal: property(props, "lethal").and_then(|v| v.as_bool()).unwrap_or(false),
+                });
+            }
+            "checkpoint" => level.checkpoints.push(object.x - origin_x),
+            "exit" => level.exit = Some(object.x - origin_x),
+            _ => {}
+        }
+    }
+
+    for layer in map.layers.iter(){
+        if let TiledLayer::Tiles{width, data, properties} = layer{
+            add_tile_layer(&mut level, map, *width, data, properties, origin_x, &image_path)?;
+        }
+    }
+
+    Ok(level)
+}
+
+fn platform_kind(props: &[TiledProperty]) -> Result<PlatformKind, anyhow::Error>{
+    let kind = match string_property(props, "kind").unwrap_or("Solid"){
+        "Solid" => PlatformKind::Solid,
+        "OneWay" => PlatformKind::OneWay,
+        "Moving" => {
+            //goes out to (dx, dy) and back
+            let dx = float_property(props, "dx", 0.0);
+            let dy = float_property(props, "dy", 0.0);
+            PlatformKind::Moving{
+                path: vec![Vec2::new(dx, -dy), Vec2::ZERO],
+                speed: float_property(props, "speed", 60.0),
+            }
+        }
+        "Crumbling" => PlatformKind::Crumbling{
+            delay: float_property(props, "delay", 0.6),
+        },
+        "Bounce" => PlatformKind::Bounce{
+            speed: float_property(props, "speed", 550.0),
+        },
+        other => anyhow::bail!("unknown platform kind {}", other),
+    };
+    Ok(kind)
+}
+
+fn hazard_kind(props: &[TiledProperty]) -> Result<HazardKind, anyhow::Error>{
+    let damage = property(props, "damage").and_then(|v| v.as_i64()).unwrap_or(10) as i32;
+    let kind = match string_property(props, "kind").unwrap_or("Spikes"){
+        "Spikes" => HazardKind::Spikes{
+            damage,
+        },
+        "Blade" => HazardKind::Blade{
+            damage,
+            reach: float_property(props, "reach", 60.0),
+            period: float_property(props, "period", 2.0),
+        },
+        "FireJet" => HazardKind::FireJet{
+            damage,
+            on: float_property(props, "on", 1.0),
+            off: float_property(props, "off", 2.0),
+        },
+        "Zone" => HazardKind::Zone{
+            damage,
+            interval: float_property(props, "interval", 0.5),
+        },
+        other => anyhow::bail!("unknown hazard kind {}", other),
+    };
+    kind.check()?;
+    Ok(kind)
+}
+
+//tiles are drawn as ground. a layer with solid = true is also
+//turned into platforms, one per run of tiles in a row.
+fn add_tile_layer(
+    level: &mut Level,
+    map: &TiledMap,
+    width: u32,
+    data: &[u32],
+    properties: &[TiledProperty],
+    origin_x: f32,
+    image_path: &impl Fn(&str) -> String,
+    ) -> Result<(), anyhow::Error>{
+    let tile_w = map.tilewidth;
+    let tile_h = map.tileheight;
+    let map_h = map.height as f32 * tile_h;
+    let solid = property(properties, "solid").and_then(|v| v.as_bool()).unwrap_or(false);
+    let mut ground: Vec<(usize, GroundTiles)> = Vec::new();
+
+    for(row, cells) in data.chunks(width as usize).enumerate(){
+        let y = map_h - (row as f32 * tile_h + tile_h / 2.0);
+        let mut run_start: Option<usize> = None;
+
+        for(col, cell) in cells.iter().enumerate(){
+            let gid = cell & TILE_ID_MASK;
+            if gid != 0{
+                //the tileset with the highest firstgid not past this tile
+                let set = map.tilesets.iter().enumerate()
+                    .filter(|(_, set)| set.firstgid <= gid)
+                    .max_by_key(|(_, set)| set.firstgid);
+                let (set_index, set) = match set{
+                    Some(found) => found,
+                    None => anyhow::bail!("tile {} has no tileset", gid),
+                };
+                let image = match &set.image{
+                    Some(image) => image,
+                    None => anyhow::bail!("only tilesets embedded in the map are supported"),
+                };
+                if !ground.iter().any(|(index, _)| *index == set_index){
+                    let columns = set.columns.max(1);
+                    ground.push((set_index, GroundTiles{
+                        image: image_path(image),
+                        tile_size: Vec2::new(tile_w, tile_h),
+                        columns,
+                        rows: (set.tilecount + columns - 1) / columns,
+                        tiles: Vec::new(),
+                    }));
+                }
+                let tiles = &mut ground.iter_mut().find(|(index, _)| *index == set_index).unwrap().1;
+                tiles.tiles.push((
+                    gid - set.firstgid,
+                    col as f32 * tile_w + tile_w / 2.0 - origin_x,
+                    y,
+                ));
+            }
+
+            //close off a solid run at a gap or the end of the row
+            let filled = gid != 0;
+            if filled && run_start.is_none(){
+                run_start = Some(col);
+            }
+            let last = col + 1 == cells.len();
+            if let Some(start) = run_start{
+                if !filled || last{
+                    let end = if filled{ col + 1 }else{ col };
+                    if solid{
+                        let w = (end - start) as f32 * tile_w;
+                        level.platforms.push(PlatformSpec{
+                            x: start as f32 * tile_w + w / 2.0 - origin_x,
+                            y,
+                            w,
+                            h: tile_h,
+                            kind: PlatformKind::Solid,
+                            hidden: true, //the tiles are the art
+                        });
+                    }
+                    run_start = None;
+                }
+            }
+        }
+    }
+
+    level.ground.extend(ground.into_iter().map(|(_, tiles)| tiles));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //a little of everything. the player is at x 100, so every x
+    //comes out 100 less. the map is 10 tiles of 32 high.
+    const MAP: &str = r#"{
+        "height": 10,
+        "tilewidth": 32,
+        "tileheight": 32,
+        "properties": [{"name": "parallax", "type": "string", "value": "Distant"}],
+        "tilesets": [{"firstgid": 1, "image": "ground.png", "columns": 2, "tilecount": 4}],
+        "layers": [
+            {
+                "type": "objectgroup",
+                "objects": [
+                    {"type": "player", "x": 100, "y": 288},
+                    {"type": "platform", "x": 200, "y": 200, "width": 96, "height": 16},
+                    {"class": "platform", "x": 300, "y": 200, "width": 64, "height": 16,
+                        "properties": [{"name": "kind", "value": "OneWay"}]},
+                    {"type": "platform", "x": 400, "y": 200, "width": 64, "height": 16,
+                        "properties": [{"name": "kind", "value": "Moving"}, {"name": "dx", "value": 64},
+                            {"name": "dy", "value": 32}, {"name": "speed", "value": 80}]},
+                    {"type": "platform", "x": 500, "y": 200, "width": 64, "height": 16,
+                        "properties": [{"name": "kind", "value": "Crumbling"}, {"name": "delay", "value": 1.0}]},
+                    {"type": "platform", "x": 600, "y": 200, "width": 64, "height": 16,
+                        "properties": [{"name": "kind", "value": "Bounce"}]},
+                    {"type": "enemy", "name": "Skeleton", "x": 350, "y": 288},
+                    {"type": "enemy", "name": "whatever", "x": 450, "y": 288,
+                        "properties": [{"name": "kind", "value": "FireMage"}, {"name": "power", "value": 40}]},
+                    {"type": "pit", "x": 500, "y": 300, "width": 64, "height": 20,
+                        "properties": [{"name": "lethal", "value": true}]},
+                    {"type": "hazard", "x": 640, "y": 272, "width": 32, "height": 32,
+                        "properties": [{"name": "kind", "value": "Blade"}, {"name": "period", "value": 1.5},
+                            {"name": "damage", "value": 15}]},
+                    {"type": "checkpoint", "x": 700, "y": 288},
+                    {"type": "exit", "x": 1100, "y": 288},
+                    {"type": "scenery", "x": 0, "y": 0}
+                ]
+            },
+            {
+                "type": "tilelayer",
+                "width": 4,
+                "data": [0, 0, 0, 0, 1, 2147483649, 1073741826, 0],
+                "properties": [{"name": "solid", "value": true}]
+            },
+            {"type": "imagelayer"}
+        ]
+    }"#;
+
+    //MAP again as tiled writes it in xml
+    const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="4" height="10" tilewidth="32" tileheight="32">
+ <properties>
+  <property name="parallax" value="Distant"/>
+ </properties>
+ <tileset firstgid="1" name="ground" tilewidth="32" tileheight="32" tilecount="4" columns="2">
+  <image source="ground.png" width="64" height="64"/>
+ </tileset>
+ <objectgroup id="1" name="things">
+  <object id="1" type="player" x="100" y="288"/>
+  <object id="2" type="platform" x="200" y="200" width="96" height="16"/>
+  <object id="3" class="platform" x="300" y="200" width="64" height="16">
+   <properties><property name="kind" value="OneWay"/></properties>
+  </object>
+  <object id="4" type="platform" x="400" y="200" width="64" height="16">
+   <properties>
+    <property name="kind" value="Moving"/>
+    <property name="dx" type="int" value="64"/>
+    <property name="dy" type="int" value="32"/>
+    <property name="speed" type="int" value="80"/>
+   </properties>
+  </object>
+  <object id="5" type="platform" x="500" y="200" width="64" height="16">
+   <properties>
+    <property name="kind" value="Crumbling"/>
+    <property name="delay" type="float" value="1"/>
+   </properties>
+  </object>
+  <object id="6" type="platform" x="600" y="200" width="64" height="16">
+   <properties><property name="kind" value="Bounce"/></properties>
+  </object>
+  <object id="7" name="Skeleton" type="enemy" x="350" y="288"/>
+  <object id="8" name="whatever" type="enemy" x="450" y="288">
+   <properties>
+    <property name="kind" value="FireMage"/>
+    <property name="power" type="int" value="40"/>
+   </properties>
+  </object>
+  <object id="9" type="pit" x="500" y="300" width="64" height="20">
+   <properties><property name="lethal" type="bool" value="true"/></properties>
+  </object>
+  <object id="10" type="hazard" x="640" y="272" width="32" height="32">
+   <properties>
+    <property name="kind" value="Blade"/>
+    <property name="period" type="float" value="1.5"/>
+    <property name="damage" type="int" value="15"/>
+   </properties>
+  </object>
+  <object id="11" type="checkpoint" x="700" y="288"/>
+  <object id="12" type="exit" x="1100" y="288"/>
+  <object id="13" type="scenery" x="0" y="0"/>
+ </objectgroup>
+ <layer id="2" name="ground" width="4" height="2">
+  <properties><property name="solid" type="bool" value="true"/></properties>
+  <data encoding="csv">
+0,0,0,0,
+1,2147483649,1073741826,0
+</data>
+ </layer>
+ <imagelayer id="3" name="sky"/>
+</map>"#;
+
+    fn load(text: &str) -> Result<Level, anyhow::Error>{
+        let map: TiledMap = serde_json::from_str(text)?;
+        to_level(&map, |image| format!("levels/{}", image))
+    }
+
+    #[test]
+    fn objects(){
+        let level = load(MAP).unwrap();
+        assert!(matches!(level.parallax, ParallaxPreset::Distant));
+        assert_eq!(level.checkpoints, vec![600.0]);
+        assert_eq!(level.exit, Some(1000.0));
+        assert_eq!(level.endless_seed, None);
+
+        assert_eq!(level.enemies.len(), 2);
+        assert_eq!(level.enemies[0].kind, EnemyKind::Skeleton);
+        assert_eq!(level.enemies[0].x, 250.0);
+        assert_eq!(level.enemies[0].power, None);
+        assert_eq!(level.enemies[1].kind, EnemyKind::FireMage);
+        assert_eq!(level.enemies[1].power, Some(40));
+
+        assert_eq!(level.pits.len(), 1);
+        assert_eq!((level.pits[0].x, level.pits[0].w, level.pits[0].lethal), (400.0, 64.0, true));
+
+        assert_eq!(level.hazards.len(), 1);
+        let hazard = &level.hazards[0];
+        assert_eq!((hazard.x, hazard.y, hazard.lethal), (556.0, 32.0, false));
+        match hazard.kind{
+            HazardKind::Blade{damage, reach, period} => assert_eq!((damage, reach, period), (15, 60.0, 1.5)),
+            _ => panic!("not a blade"),
+        }
+    }
+
+    #[test]
+    fn platform_kinds(){
+        let level = load(MAP).unwrap();
+        //the objects first, then the solid tile run
+        let kinds: Vec<&PlatformKind> = level.platforms.iter().map(|p| &p.kind).collect();
+        assert_eq!(kinds, vec![
+            &PlatformKind::Solid,
+            &PlatformKind::OneWay,
+            &PlatformKind::Moving{path: vec![Vec2::new(64.0, -32.0), Vec2::ZERO], speed: 80.0},
+            &PlatformKind::Crumbling{delay: 1.0},
+            &PlatformKind::Bounce{speed: 550.0},
+            &PlatformKind::Solid,
+        ]);
+        //tiled's top left corner to our center above the bottom
+        let first = &level.platforms[0];
+        assert_eq!((first.x, first.y, first.w, first.h, first.hidden), (148.0, 112.0, 96.0, 16.0, false));
+    }
+
+    #[test]
+    fn tiles_drop_flip_flags(){
+        let level = load(MAP).unwrap();
+        assert_eq!(level.ground.len(), 1);
+        let ground = &level.ground[0];
+        assert_eq!(ground.image, "levels/ground.png");
+        assert_eq!((ground.columns, ground.rows), (2, 2));
+        //flipped flat and flipped up are still tiles 0 and 1 of the sheet
+        assert_eq!(ground.tiles, vec![(0, -84.0, 272.0), (0, -52.0, 272.0), (1, -20.0, 272.0)]);
+
+        //the three in a row are one hidden platform
+        let run = level.platforms.last().unwrap();
+        assert_eq!((run.x, run.y, run.w, run.h, run.hidden), (-52.0, 272.0, 96.0, 32.0, true));
+    }
+
+    #[test]
+    fn unknown_kinds_fail(){
+        let wobbly = MAP.replace("\"OneWay\"", "\"Wobbly\"");
+        assert!(load(&wobbly).is_err());
+        let goblin = MAP.replace("\"FireMage\"", "\"Goblin\"");
+        assert!(load(&goblin).is_err());
+    }
+
+    #[test]
+    fn traps_need_time(){
+        let still = MAP.replace("{\"name\": \"period\", \"value\": 1.5}", "{\"name\": \"period\", \"value\": 0}");
+        assert!(load(&still).err().unwrap().to_string().contains("blade period"));
+        let jet = MAP.replace("\"Blade\"", "\"FireJet\"").replace("\"period\"", "\"off\"")
+            .replace("\"value\": 1.5", "\"value\": -1");
+        assert!(load(&jet).err().unwrap().to_string().contains("fire jet off"));
+    }
+
+    #[test]
+    fn tmx_reads_like_json(){
+        let json = load(MAP).unwrap();
+        let tmx = to_level(&read_tmx(TMX).unwrap(), |image| format!("levels/{}", image)).unwrap();
+        assert!(matches!(tmx.parallax, ParallaxPreset::Distant));
+        assert_eq!(tmx.platforms, json.platforms);
+        assert_eq!(tmx.checkpoints, json.checkpoints);
+        assert_eq!(tmx.exit, json.exit);
+        let enemies = |level: &Level| level.enemies.iter().map(|e| (e.kind, e.x, e.power)).collect::<Vec<_>>();
+        assert_eq!(enemies(&tmx), enemies(&json));
+        assert_eq!((tmx.pits[0].x, tmx.pits[0].w, tmx.pits[0].lethal), (400.0, 64.0, true));
+        match tmx.hazards[0].kind{
+            HazardKind::Blade{damage, reach, period} => assert_eq!((damage, reach, period), (15, 60.0, 1.5)),
+            _ => panic!("not a blade"),
+        }
+        assert_eq!(tmx.ground[0].image, json.ground[0].image);
+        assert_eq!(tmx.ground[0].tiles, json.ground[0].tiles);
+    }
+
+    #[test]
+    fn tmx_says_what_it_cant_read(){
+        let zipped = TMX.replace("encoding=\"csv\"", "encoding=\"base64\" compression=\"zlib\"");
+        assert!(read_tmx(&zipped).err().unwrap().to_string().contains("CSV"));
+        assert!(read_tmx("<tileset/>").is_err());
+    }
+}
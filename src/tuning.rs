@@ -0,0 +1,416 @@
+use bevy::prelude::*;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, HashMap};
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::{Player, Enemy, Body, Energy, Skeleton, Mage};
+use crate::backgrounds::Layer;
+use crate::loading::LoadingAssets;
+
+const TUNING: &str = "game.tuning.ron";
+
+//numbers worth trying out without a rebuild. the file in assets is
+//watched, every change is put onto what is already in the world.
+//anything left out of the file keeps the value below.
+#[derive(Deserialize, TypeUuid, Clone)]
+#[uuid = "30c77a14-d3e6-4fda-8f18-edaef8b1a974"]
+#[serde(default)]
+pub(crate) struct Tuning{
+    pub(crate) physics: PhysicsTuning,
+    pub(crate) parallax: HashMap<String, f32>, //depth of each background layer, by texture id
+    pub(crate) player_animation: PlayerAnimation,
+    pub(crate) skeleton_animation: SkeletonAnimation,
+    pub(crate) mage_animation: MageAnimation,
+    pub(crate) boss_animation: BossAnimation,
+    pub(crate) archetypes: HashMap<String, Archetype>, //enemy stats, by kind
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub(crate) struct PhysicsTuning{
+    pub(crate) gravity: f32, //px/s/s
+    pub(crate) jump_speed: f32,
+    pub(crate) chop_hop: f32, //the little hop a chop starts with
+    pub(crate) player_body: BodyTuning,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub(crate) struct BodyTuning{
+    pub(crate) accel: f32,
+    pub(crate) decel: f32,
+    pub(crate) air_control: f32,
+}
+
+//frames of the player sheet. Fly uses the jump frames.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub(crate) struct PlayerAnimation{
+    pub(crate) frame_time: f32, //seconds on each frame
+    pub(crate) stand: u32,
+    pub(crate) walk: Clip,
+    pub(crate) charge: Clip,
+    pub(crate) chop: Clip,
+    pub(crate) block: Clip,
+    pub(crate) jump: Clip,
+}
+
+//frames of the skeleton sheet, laid out like the player's.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub(crate) struct SkeletonAnimation{
+    pub(crate) frame_time: f32,
+    pub(crate) stand: u32,
+    pub(crate) walk: Clip,
+    pub(crate) charge: Clip,
+    pub(crate) chop: Clip,
+    pub(crate) block: Clip,
+}
+
+//frames of the mage sheets. the spell leaves the staff on release.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub(crate) struct MageAnimation{
+    pub(crate) frame_time: f32,
+    pub(crate) idle: Clip,
+    pub(crate) walk: Clip,
+    pub(crate) cast: Clip,
+    pub(crate) release: u32,
+}
+
+//frames of the minotaur sheet. the taunt, attacks and death play
+//once, the wind-up holds its last frame.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub(crate) struct BossAnimation{
+    pub(crate) frame_time: f32,
+    pub(crate) idle: Clip,
+    pub(crate) walk: Clip,
+    pub(crate) taunt: Clip,
+    pub(crate) wind_up: Clip,
+    pub(crate) heavy: Swing,
+    pub(crate) stab: Swing,
+    pub(crate) spin: Swing,
+    pub(crate) dying: Clip,
+}
+
+//an attack, and the frames of it where the axe can connect, first to last.
+#[derive(Deserialize, Clone, Copy)]
+pub(crate) struct Swing{
+    pub(crate) frames: Clip,
+    pub(crate) hits: (u32, u32),
+}
+
+//first to last in order, then back to first. hold goes back to
+//that frame instead, for a pose kept as long as a key is down.
+#[derive(Deserialize, Clone, Copy)]
+pub(crate) struct Clip{
+    pub(crate) first: u32,
+    pub(crate) last: u32,
+    #[serde(default)]
+    pub(crate) hold: Option<u32>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub(crate) struct Archetype{
+    pub(crate) power: i32,
+    pub(crate) body: BodyTuning,
+}
+
+impl Clip{
+    pub(crate) fn next(&self, index: u32) -> u32{
+        if index >= self.first && index < self.last{
+            index + 1
+        }else if index == self.last{
+            self.hold.unwrap_or(self.first)
+        }else{
+            self.first
+        }
+    }
+}
+
+impl BodyTuning{
+    pub(crate) fn body(&self) -> Body{
+        Body{
+            target_vx: 0.0,
+            accel: self.accel,
+            decel: self.decel,
+            air_control: self.air_control,
+        }
+    }
+
+    fn apply(&self, body: &mut Body){
+        body.accel = self.accel;
+        body.decel = self.decel;
+        body.air_control = self.air_control;
+    }
+}
+
+impl Tuning{
+    pub(crate) fn archetype(&self, kind: &str) -> Archetype{
+        match self.archetypes.get(kind){
+            Some(archetype) => *archetype,
+            None => {
+                warn!("no archetype called {} in {}", kind, TUNING);
+                Tuning::default().archetypes[kind]
+            }
+        }
+    }
+
+    pub(crate) fn depth(&self, layer: &str) -> f32{
+        match self.parallax.get(layer){
+            Some(depth) => *depth,
+            None => Tuning::default().parallax.get(layer).copied().unwrap_or(0.0),
+        }
+    }
+}
+
+impl Default for Tuning{
+    fn default() -> Self{
+        let mut parallax = HashMap::default();
+        for(layer, depth) in [("background", 0.0), ("bg_02", 0.5), ("bg_03", 0.7), ("bg_10", 0.8),
+            ("bg_04", 1.0), ("bg_05", 1.3), ("bg_06", 1.5), ("bg_07", 1.5), ("bg_08", 1.6),
+            ("bg_09", 1.9)].iter(){
+            parallax.insert(layer.to_string(), *depth);
+        }
+        let enemy_body = BodyTuning{
+            accel: 800.0,
+            decel: 1500.0,
+            air_control: 0.3,
+        };
+        let mut archetypes = HashMap::default();
+        archetypes.insert("skeleton".to_string(), Archetype{
+            power: 25, //skelly is low energy.
+            body: enemy_body,
+        });
+        archetypes.insert("mage".to_string(), Archetype{
+            power: 15, //mages are fragile up close.
+            body: enemy_body,
+        });
+        Tuning{
+            physics: PhysicsTuning::default(),
+            parallax,
+            player_animation: PlayerAnimation::default(),
+            skeleton_animation: SkeletonAnimation::default(),
+            mage_animation: MageAnimation::default(),
+            boss_animation: BossAnimation::default(),
+            archetypes,
+        }
+    }
+}
+
+impl Default for PhysicsTuning{
+    fn default() -> Self{
+        PhysicsTuning{
+            gravity: 800.0,
+            jump_speed: 333.0,
+            chop_hop: 222.0,
+            player_body: BodyTuning{
+                accel: 1200.0,
+                decel: 1500.0,
+                air_control: 0.4,
+            },
+        }
+    }
+}
+
+impl Default for PlayerAnimation{
+    fn default() -> Self{
+        PlayerAnimation{
+            frame_time: 0.1,
+            stand: 18,
+            walk: Clip{ first: 18, last: 21, hold: None },
+            charge: Clip{ first: 14, last: 17, hold: None },
+            chop: Clip{ first: 27, last: 30, hold: None },
+            block: Clip{ first: 23, last: 26, hold: Some(25) },
+            jump: Clip{ first: 23, last: 26, hold: None },
+        }
+    }
+}
+
+impl Default for SkeletonAnimation{
+    fn default() -> Self{
+        SkeletonAnimation{
+            frame_time: 0.1,
+            stand: 18,
+            walk: Clip{ first: 18, last: 21, hold: None },
+            charge: Clip{ first: 14, last: 17, hold: None },
+            chop: Clip{ first: 27, last: 30, hold: None },
+            block: Clip{ first: 23, last: 26, hold: None },
+        }
+    }
+}
+
+impl Default for MageAnimation{
+    fn default() -> Self{
+        //7 across, row 0 idle, row 1 walk, row 2 cast
+        MageAnimation{
+            frame_time: 0.1,
+            idle: Clip{ first: 0, last: 3, hold: None },
+            walk: Clip{ first: 7, last: 10, hold: None },
+            cast: Clip{ first: 14, last: 20, hold: None },
+            release: 19,
+        }
+    }
+}
+
+impl Default for BossAnimation{
+    fn default() -> Self{
+        //10 across, row 0 idle, 1 walk, 2 taunt, 3 heavy, 4 stab,
+        //5 axe raise, 6 spin, 9 death
+        BossAnimation{
+            frame_time: 0.1,
+            idle: Clip{ first: 0, last: 4, hold: None },
+            walk: Clip{ first: 10, last: 17, hold: None },
+            taunt: Clip{ first: 20, last: 24, hold: None },
+            wind_up: Clip{ first: 50, last: 55, hold: Some(55) },
+            heavy: Swing{ frames: Clip{ first: 30, last: 38, hold: None }, hits: (31, 34) },
+            stab: Swing{ frames: Clip{ first: 40, last: 44, hold: None }, hits: (41, 43) },
+            spin: Swing{ frames: Clip{ first: 60, last: 68, hold: None }, hits: (61, 66) },
+            dying: Clip{ first: 90, last: 95, hold: None },
+        }
+    }
+}
+
+#[derive(Default)]
+struct TuningLoader;
+
+impl AssetLoader for TuningLoader{
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+        ) -> BoxedFuture<'a, Result<(), anyhow::Error>>{
+        Box::pin(async move{
+            let tuning: Tuning = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(tuning));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str]{
+        &["tuning.ron"]
+    }
+}
+
+struct TuningFile{
+    handle: Handle<Tuning>,
+}
+
+pub struct TuningPlugin;
+impl Plugin for TuningPlugin{
+    fn build(&self, app: &mut AppBuilder){
+        app
+            .add_asset::<Tuning>()
+            .init_asset_loader::<TuningLoader>()
+            //what spawns before the file is in uses this, and is
+            //brought up to date once it is
+            .insert_resource(Tuning::default())
+            .add_startup_system(load_tuning.system())
+            .add_system(apply_tuning.system());
+    }
+}
+
+fn load_tuning(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut loading: ResMut<LoadingAssets>,
+    ){
+    commands.insert_resource(TuningFile{
+        handle: loading.load(&asset_server, TUNING),
+    });
+}
+
+//systems read the Tuning resource as they go. what was already set up
+//from it at spawn is changed here.
+fn apply_tuning(
+    mut tuning_events: EventReader<AssetEvent<Tuning>>,
+    tunings: Res<Assets<Tuning>>,
+    file: Res<TuningFile>,
+    mut tuning: ResMut<Tuning>,
+    mut player_query: Query<(&mut Body, &mut Timer), With<Player>>,
+    mut enemy_query: Query<(&mut Body, &mut Energy, &mut Timer, Option<&Skeleton>, Option<&Mage>),
+        (With<Enemy>, Without<Player>)>,
+    mut layer_query: Query<(&Layer, &mut Transform)>,
+    ){
+    let mut changed = false;
+    for event in tuning_events.iter(){
+        match event{
+            AssetEvent::Created{handle} if *handle == file.handle => changed = true,
+            AssetEvent::Modified{handle} if *handle == file.handle => {
+                info!("reloaded {}", TUNING);
+                changed = true;
+            }
+            _ => {}
+        }
+    }
+    if !changed{
+        return;
+    }
+    let new = match tunings.get(&file.handle){
+        Some(new) => new.clone(),
+        None => return,
+    };
+
+    for(mut body, mut timer) in player_query.iter_mut(){
+        new.physics.player_body.apply(&mut body);
+        timer.set_duration(Duration::from_secs_f32(new.player_animation.frame_time));
+    }
+
+    for(mut body, mut energy, mut timer, skeleton, mage) in enemy_query.iter_mut(){
+        let (kind, frame_time) = match (skeleton, mage){
+            (Some(_), _) => ("skeleton", new.skeleton_animation.frame_time),
+            (_, Some(_)) => ("mage", new.mage_animation.frame_time),
+            _ => {
+                //only the boss's animation is tuned
+                timer.set_duration(Duration::from_secs_f32(new.boss_animation.frame_time));
+                continue;
+            }
+        };
+        timer.set_duration(Duration::from_secs_f32(frame_time));
+        let (old, archetype) = (tuning.archetype(kind), new.archetype(kind));
+        archetype.body.apply(&mut body);
+        //a level can set an enemy's power itself, leave those be.
+        //damage already taken stays taken, but a reload never kills.
+        if energy.max == old.power{
+            let taken = energy.max - energy.power;
+            energy.max = archetype.power;
+            energy.power = (archetype.power - taken).max(1);
+        }
+    }
+
+    for(layer, mut transform) in layer_query.iter_mut(){
+        transform.translation.z = new.depth(layer.id);
+    }
+
+    *tuning = new;
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn clip_loops_back_to_first(){
+        let walk = Clip{ first: 18, last: 21, hold: None };
+        assert_eq!(walk.next(18), 19);
+        assert_eq!(walk.next(20), 21);
+        assert_eq!(walk.next(21), 18);
+    }
+
+    #[test]
+    fn clip_holds_its_pose(){
+        let block = Clip{ first: 23, last: 26, hold: Some(25) };
+        assert_eq!(block.next(25), 26);
+        assert_eq!(block.next(26), 25);
+    }
+
+    #[test]
+    fn clip_starts_from_any_other_frame(){
+        //switching clips picks up wherever the last one was
+        let chop = Clip{ first: 27, last: 30, hold: None };
+        assert_eq!(chop.next(18), 27);
+        assert_eq!(chop.next(31), 27);
+    }
+}